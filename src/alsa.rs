@@ -1,13 +1,19 @@
 use async_trait::async_trait;
 
-use crate::{Widget, WidgetOutput};
+use crate::{Icon, IconPack, Metric, Template, TemplateError, Widget, WidgetOutput, WidgetState};
 use std::time::Duration;
 
+/// Volume of the ALSA `Master` channel, in red italics while muted.
+///
+/// Format placeholders: `{icon}`, `{volume}`.
 pub struct Alsa {
     interval: Duration,
-    icon: String,
+    icons: IconPack,
+    format: Template,
 }
 
+const PLACEHOLDERS: &[&str] = &["icon", "volume"];
+
 #[async_trait]
 impl Widget for Alsa {
     fn interval(&self) -> Duration {
@@ -19,17 +25,35 @@ impl Widget for Alsa {
 
         match Self::get_volume() {
             Some((vol, is_muted)) => {
+                let level = if is_muted { 0.0 } else { f64::from(vol) };
+                let text = self.format.render(|name| match name {
+                    "icon" => self.icons.icon(Icon::Volume, level).into(),
+                    _ => f64::from(vol).into(),
+                });
                 let text = if is_muted {
                     use_default_foreground = true;
-                    format!("<span foreground='red'><i>{} {}</i></span>", self.icon, vol)
+                    format!("<span foreground='red'><i>{}</i></span>", text)
                 } else {
-                    format!("{} {}", self.icon, vol)
+                    text
                 };
 
                 WidgetOutput {
                     text,
                     use_default_foreground,
                     use_default_background,
+                    state: if is_muted {
+                        WidgetState::State("muted")
+                    } else {
+                        WidgetState::Normal
+                    },
+                    metrics: vec![
+                        Metric::gauge("volume_percent", "Master volume in percent", f64::from(vol)),
+                        Metric::gauge(
+                            "volume_muted",
+                            "Whether the master channel is muted",
+                            if is_muted { 1.0 } else { 0.0 },
+                        ),
+                    ],
                 }
             }
             None => WidgetOutput {
@@ -39,6 +63,8 @@ impl Widget for Alsa {
                 ),
                 use_default_foreground,
                 use_default_background,
+                state: WidgetState::Error,
+                metrics: vec![],
             },
         }
     }
@@ -48,10 +74,21 @@ impl Alsa {
     pub fn new(interval: Duration) -> Self {
         Self {
             interval,
-            icon: "\u{f2a0}".to_string(),
+            icons: IconPack::default(),
+            format: Template::parse("{icon} {volume}", PLACEHOLDERS).unwrap(),
         }
     }
 
+    pub fn icons(mut self, icons: IconPack) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
+    }
+
     fn get_volume() -> Option<(u8, bool)> {
         let r = std::process::Command::new("sh")
             .arg("-c")
@@ -0,0 +1,337 @@
+use crate::markup;
+use crate::sink::Sink;
+use crate::{WidgetOutput, WidgetState};
+
+use std::time::{Duration, Instant};
+
+/// Handle of a widget registered with `Barr::add_widget`, used to place the
+/// same widget instance on several bars.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct WidgetId(pub(crate) usize);
+
+/// Rule to leave a widget out of a bar, separator included.
+#[derive(Debug, Clone)]
+pub enum HideWhen {
+    /// The widget failed to read its value
+    Error,
+    /// The widget reports this state, e.g. `"disconnected"` for `Wifi`
+    State(&'static str),
+    /// Every sample of the named metric is below the value, e.g.
+    /// `Below("network_bytes_per_second", 1024.0)`
+    Below(&'static str, f64),
+}
+
+impl HideWhen {
+    fn matches(&self, out: &WidgetOutput) -> bool {
+        match self {
+            HideWhen::Error => out.state == WidgetState::Error,
+            HideWhen::State(state) => out.state == WidgetState::State(state),
+            HideWhen::Below(name, value) => {
+                let mut samples = out.metrics.iter().filter(|m| m.name == *name).peekable();
+                samples.peek().is_some() && samples.all(|m| m.value < *value)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// A widget placed on a bar together with how it is displayed there.
+#[derive(Debug, Clone)]
+pub struct Slot {
+    widget: WidgetId,
+    hide_when: Vec<HideWhen>,
+    min_width: usize,
+    align: Align,
+    max_width: Option<usize>,
+    marquee: Option<f64>,
+    started: Instant,
+}
+
+impl Slot {
+    pub fn new(widget: WidgetId) -> Self {
+        Self {
+            widget,
+            hide_when: vec![],
+            min_width: 0,
+            align: Align::Left,
+            max_width: None,
+            marquee: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// Pad the text to at least `min_width` display columns so the widgets
+    /// after it don't move as the value changes width.
+    pub fn min_width(mut self, min_width: usize) -> Self {
+        self.min_width = min_width;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Cut the text to `max_width` display columns with an ellipsis.
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// Scroll text longer than `max_width` by `speed` columns per second
+    /// instead of cutting it.
+    pub fn marquee(mut self, speed: f64) -> Self {
+        self.marquee = Some(speed);
+        self
+    }
+
+    /// Hide the widget while any of its rules match.
+    pub fn hide_when(mut self, rule: HideWhen) -> Self {
+        self.hide_when.push(rule);
+        self
+    }
+
+    fn is_visible(&self, out: &WidgetOutput) -> bool {
+        !self.hide_when.iter().any(|rule| rule.matches(out))
+    }
+
+    fn layout(&self, text: &str) -> String {
+        let text = match self.max_width {
+            Some(max_width) if markup::width(text) > max_width => match self.marquee {
+                Some(speed) => self.scroll(text, max_width, speed),
+                None => markup::truncate(text, max_width),
+            },
+            _ => text.to_string(),
+        };
+
+        let width = markup::width(&text);
+        if width >= self.min_width {
+            return text;
+        }
+
+        let pad = self.min_width - width;
+        let (left, right) = match self.align {
+            Align::Left => (0, pad),
+            Align::Right => (pad, 0),
+            Align::Center => (pad / 2, pad - pad / 2),
+        };
+        format!("{}{}{}", " ".repeat(left), text, " ".repeat(right))
+    }
+
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    fn scroll(&self, text: &str, max_width: usize, speed: f64) -> String {
+        let gap = "   ";
+        let cycle = markup::width(text) + gap.len();
+        let offset = (self.started.elapsed().as_secs_f64() * speed) as usize % cycle;
+
+        // The text followed by itself, so the window wraps around smoothly
+        let looped = format!("{}{}{}", text, gap, text);
+        markup::slice(&looped, offset, max_width)
+    }
+}
+
+impl From<WidgetId> for Slot {
+    fn from(widget: WidgetId) -> Self {
+        Self::new(widget)
+    }
+}
+
+/// A layout of widgets rendered to its own sink.
+pub struct Bar {
+    sink: Box<dyn Sink>,
+    slots: Vec<Slot>,
+}
+
+impl Bar {
+    pub fn new(sink: impl Sink + 'static) -> Self {
+        Self {
+            sink: Box::new(sink),
+            slots: vec![],
+        }
+    }
+
+    pub fn add_widget(&mut self, slot: impl Into<Slot>) {
+        self.slots.push(slot.into());
+    }
+
+    pub(crate) fn contains(&self, widget: WidgetId) -> bool {
+        self.slots.iter().any(|slot| slot.widget == widget)
+    }
+
+    /// How often the bar has to be redrawn to keep its marquees moving.
+    pub(crate) fn marquee_interval(&self) -> Option<Duration> {
+        self.slots
+            .iter()
+            .filter(|slot| slot.max_width.is_some())
+            .filter_map(|slot| slot.marquee)
+            .filter(|speed| *speed > 0.0)
+            .map(|speed| Duration::from_secs_f64(1.0 / speed))
+            .min()
+    }
+
+    pub(crate) fn show(&mut self, outputs: &[Option<WidgetOutput>]) {
+        let text = self.render(outputs);
+        self.sink.show(&text);
+    }
+
+    fn render(&self, outputs: &[Option<WidgetOutput>]) -> String {
+        let black = "#0F1419";
+        let white = "white";
+        let sep = "\u{e0b0}";
+
+        // Colors alternate over the visible widgets only
+        let visible = self.slots.iter().filter_map(|slot| {
+            outputs[slot.widget.0]
+                .as_ref()
+                .filter(|out| slot.is_visible(out))
+                .map(|out| (slot, out))
+        });
+
+        let mut text = String::new();
+        for (i, (slot, out)) in visible.enumerate() {
+            let body = slot.layout(&out.text);
+
+            let (fg, bg) = if i % 2 == 0 {
+                (black, white)
+            } else {
+                (white, black)
+            };
+
+            let mut out_text = if out.use_default_foreground && out.use_default_background {
+                format!(
+                    "<span background='{}' foreground='{}'> {} </span>",
+                    bg, fg, body
+                )
+            } else if out.use_default_foreground {
+                format!("<span foreground='{}'> {} </span>", fg, body)
+            } else if out.use_default_background {
+                format!("<span foreground='{}'> {} </span>", bg, body)
+            } else {
+                body
+            };
+            if !(i == 0 && fg == white) {
+                out_text = format!(
+                    "<span background='{}' foreground='{}'>{}</span>{}",
+                    bg, fg, sep, out_text
+                );
+            }
+            text.push_str(&out_text);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::Stdout;
+    use crate::Metric;
+
+    fn output(text: &str, state: WidgetState) -> Option<WidgetOutput> {
+        Some(WidgetOutput {
+            text: text.to_string(),
+            use_default_foreground: true,
+            use_default_background: true,
+            state,
+            metrics: vec![],
+        })
+    }
+
+    #[test]
+    fn hidden_slots_leave_no_trace() {
+        let outputs = [
+            output("cpu", WidgetState::Normal),
+            output("wifi", WidgetState::State("disconnected")),
+            output("date", WidgetState::Normal),
+        ];
+        let mut bar = Bar::new(Stdout);
+        bar.add_widget(WidgetId(0));
+        bar.add_widget(Slot::new(WidgetId(1)).hide_when(HideWhen::State("disconnected")));
+        bar.add_widget(WidgetId(2));
+
+        let mut visible = Bar::new(Stdout);
+        visible.add_widget(WidgetId(0));
+        visible.add_widget(WidgetId(2));
+
+        let text = bar.render(&outputs);
+        assert_eq!(text, visible.render(&outputs));
+        assert!(!text.contains("wifi"));
+        assert_eq!(text.matches('\u{e0b0}').count(), 2);
+        // The widget after the hidden one takes its colors
+        assert!(text.ends_with("<span background='#0F1419' foreground='white'> date </span>"));
+    }
+
+    #[test]
+    fn hide_below_a_metric() {
+        let slot = Slot::new(WidgetId(0)).hide_when(HideWhen::Below("network_rx_bytes", 1024.0));
+        let mut out = output("net", WidgetState::Normal).unwrap();
+        // Without the metric there is nothing to compare
+        assert!(slot.is_visible(&out));
+
+        out.metrics = vec![
+            Metric::counter("network_rx_bytes", "", 10.0),
+            Metric::counter("network_rx_bytes", "", 2048.0),
+        ];
+        assert!(slot.is_visible(&out));
+        out.metrics.pop();
+        assert!(!slot.is_visible(&out));
+    }
+
+    #[test]
+    fn hide_on_error() {
+        let slot = Slot::new(WidgetId(0)).hide_when(HideWhen::Error);
+        assert!(slot.is_visible(&output("bat", WidgetState::Normal).unwrap()));
+        assert!(!slot.is_visible(&output("bat", WidgetState::Error).unwrap()));
+    }
+
+    #[test]
+    fn pads_to_min_width() {
+        let slot = |align| Slot::new(WidgetId(0)).min_width(6).align(align);
+        assert_eq!(slot(Align::Left).layout("abc"), "abc   ");
+        assert_eq!(slot(Align::Right).layout("abc"), "   abc");
+        // The odd column goes to the right
+        assert_eq!(slot(Align::Center).layout("abc"), " abc  ");
+        assert_eq!(slot(Align::Left).layout("abcdefgh"), "abcdefgh");
+    }
+
+    #[test]
+    fn min_width_counts_display_columns() {
+        let slot = Slot::new(WidgetId(0)).min_width(5).align(Align::Right);
+        assert_eq!(slot.layout("<b>日本</b>"), " <b>日本</b>");
+        assert_eq!(slot.layout("a&amp;b"), "  a&amp;b");
+        assert_eq!(
+            slot.layout("<span foreground='red'>12345</span>"),
+            "<span foreground='red'>12345</span>"
+        );
+    }
+
+    #[test]
+    fn truncates_to_max_width() {
+        let slot = Slot::new(WidgetId(0)).max_width(5);
+        assert_eq!(slot.layout("short"), "short");
+        assert_eq!(slot.layout("<b>too long</b>"), "<b>too </b>…");
+    }
+
+    #[test]
+    fn marquee_wraps_across_the_gap() {
+        let mut slot = Slot::new(WidgetId(0)).max_width(6).marquee(1.0);
+        let scrolled = |slot: &mut Slot, secs| {
+            slot.started = Instant::now() - Duration::from_secs(secs);
+            slot.layout("abcdefgh")
+        };
+
+        assert_eq!(scrolled(&mut slot, 0), "abcdef");
+        assert_eq!(scrolled(&mut slot, 5), "fgh   ");
+        assert_eq!(scrolled(&mut slot, 9), "  abcd");
+        // A full cycle of the text and the gap
+        assert_eq!(scrolled(&mut slot, 11), "abcdef");
+        // Short enough text stays put
+        assert_eq!(slot.layout("abc"), "abc");
+    }
+}
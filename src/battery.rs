@@ -1,6 +1,8 @@
 #![allow(clippy::non_ascii_literal)]
 
-use crate::{Widget, WidgetOutput};
+use crate::{
+    Icon, IconPack, Metric, Template, TemplateError, Thresholds, Widget, WidgetOutput, WidgetState,
+};
 use async_trait::async_trait;
 use battery::State;
 use notify_rust::{Notification, NotificationUrgency, Timeout};
@@ -8,10 +10,18 @@ use std::fmt::{self, Display, Formatter};
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
+/// State of charge of the first battery, with desktop notifications when it
+/// is critical or fully charged.
+///
+/// Format placeholders (`format` while discharging, `charging_format` while
+/// charging): `{icon}`, `{percent}`.
 #[derive(Debug)]
 pub struct Battery {
     interval: Duration,
-    tresholds: Vec<(f64, String)>,
+    thresholds: Thresholds,
+    icons: IconPack,
+    format: Template,
+    charging_format: Template,
 
     ac_color: String,
     charging_color: String,
@@ -19,6 +29,8 @@ pub struct Battery {
     last_notify_full: Mutex<Option<Instant>>,
 }
 
+const PLACEHOLDERS: &[&str] = &["icon", "percent"];
+
 #[derive(Debug)]
 struct BatteryInfo {
     state: battery::State,
@@ -58,7 +70,11 @@ impl Widget for Battery {
                                 }
                             }
 
-                            format!("<span foreground='{}'><b>︇</b></span>", self.ac_color)
+                            format!(
+                                "<span foreground='{}'><b>{}</b></span>",
+                                self.ac_color,
+                                self.icons.icon(Icon::Plugged, 100.0)
+                            )
                         }
                         State::Charging => {
                             // Reset notification immidiately after charged
@@ -68,8 +84,9 @@ impl Widget for Battery {
 
                             use_default_fg = false;
                             format!(
-                                "<span foreground='{}'>[C] {:.0}</span>",
-                                self.charging_color, info.value,
+                                "<span foreground='{}'>{}</span>",
+                                self.charging_color,
+                                self.render(&self.charging_format, Icon::Charging, &info),
                             )
                         }
                         State::Discharging => {
@@ -77,53 +94,71 @@ impl Widget for Battery {
                             let mut last_notify_full = self.last_notify_full.lock().unwrap();
                             *last_notify_full = None;
 
-                            let fg = {
-                                let mut fg = None;
-                                for (i, (treshold, color)) in self.tresholds.iter().enumerate() {
-                                    if f64::from(info.value) <= *treshold {
-                                        fg = Some(color);
-
-                                        if i > 0 {
-                                            break;
-                                        }
-
-                                        // Notify when `i == 0` (lowest treshold)
-                                        let now = Instant::now();
-                                        let mut last_notify =
-                                            self.last_notify_critical.lock().unwrap();
-
-                                        if *last_notify == None {
-                                            *last_notify = Some(now);
-                                            Self::notify_critical();
-                                        } else {
-                                            let diff: Duration = now - (*last_notify).unwrap();
-
-                                            if diff.as_secs() >= 60 {
-                                                *last_notify = Some(now);
-                                                Self::notify_critical();
-                                            }
-                                        }
-                                        break;
+                            let value = f64::from(info.value);
+
+                            // Notify at the lowest (most critical) treshold
+                            let level = self.thresholds.level(value);
+                            if level == Some(0) {
+                                let now = Instant::now();
+                                let mut last_notify = self.last_notify_critical.lock().unwrap();
+
+                                if last_notify.is_none() {
+                                    *last_notify = Some(now);
+                                    Self::notify_critical();
+                                } else {
+                                    let diff: Duration = now - (*last_notify).unwrap();
+
+                                    if diff.as_secs() >= 60 {
+                                        *last_notify = Some(now);
+                                        Self::notify_critical();
                                     }
                                 }
-                                fg
-                            };
+                            }
+                            let fg = self.thresholds.level_color(level, value);
+                            let text = self.render(&self.format, Icon::Battery, &info);
                             if let Some(fg) = fg {
-                                format!("<span foreground='{}'> {:.0}</span>", fg, info.value)
+                                format!("<span foreground='{}'>{}</span>", fg, text)
                             } else {
-                                format!(" {:.0}", info.value)
+                                text
                             }
                         }
                         State::Empty | State::__Nonexhaustive => {
                             use_default_fg = false;
-                            format!("<span foreground='{}'><b>︇</b></span>", "red")
+                            format!(
+                                "<span foreground='{}'><b>{}</b></span>",
+                                "red",
+                                self.icons.icon(Icon::Battery, 0.0)
+                            )
                         }
                     }
                 };
+                let state = match info.state {
+                    State::Unknown | State::Full => WidgetState::State("full"),
+                    State::Charging => WidgetState::State("charging"),
+                    State::Discharging => WidgetState::State("discharging"),
+                    State::Empty | State::__Nonexhaustive => WidgetState::State("empty"),
+                };
                 WidgetOutput {
                     text,
                     use_default_foreground: use_default_fg,
                     use_default_background: true,
+                    state,
+                    metrics: vec![
+                        Metric::gauge(
+                            "battery_charge_percent",
+                            "Battery state of charge in percent",
+                            f64::from(info.value),
+                        ),
+                        Metric::gauge(
+                            "battery_charging",
+                            "Whether the battery is charging",
+                            if info.state == State::Charging {
+                                1.0
+                            } else {
+                                0.0
+                            },
+                        ),
+                    ],
                 }
             }
 
@@ -131,6 +166,8 @@ impl Widget for Battery {
                 text: "<span foreground='grey'>No Battery</span>".to_string(),
                 use_default_foreground: true,
                 use_default_background: true,
+                state: WidgetState::Error,
+                metrics: vec![],
             },
         }
     }
@@ -148,16 +185,46 @@ impl Battery {
             interval,
             ac_color,
             charging_color,
-            tresholds: vec![
-                (25_f64, "#FF0000".to_string()),
-                (35_f64, "#F2665F".to_string()),
-                (50_f64, "#E9A072".to_string()),
-            ],
+            icons: IconPack::default(),
+            format: Template::parse("{icon} {percent}", PLACEHOLDERS).unwrap(),
+            charging_format: Template::parse("[C] {percent}", PLACEHOLDERS).unwrap(),
+            thresholds: Thresholds::descending(&[
+                (25_f64, "#FF0000"),
+                (35_f64, "#F2665F"),
+                (50_f64, "#E9A072"),
+            ]),
             last_notify_critical: Mutex::new(None),
             last_notify_full: Mutex::new(None),
         }
     }
 
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
+    }
+
+    pub fn charging_format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.charging_format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
+    }
+
+    pub fn thresholds(mut self, thresholds: Thresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    pub fn icons(mut self, icons: IconPack) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    fn render(&self, format: &Template, icon: Icon, info: &BatteryInfo) -> String {
+        format.render(|name| match name {
+            "icon" => self.icons.icon(icon, f64::from(info.value)).into(),
+            _ => f64::from(info.value).into(),
+        })
+    }
+
     fn battery_stat() -> Result<BatteryInfo, battery::Error> {
         let battery = battery::Manager::new()?.batteries()?.next().unwrap()?;
 
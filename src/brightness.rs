@@ -1,13 +1,19 @@
-use crate::{Widget, WidgetOutput};
+use crate::{Icon, IconPack, Template, TemplateError, Widget, WidgetOutput, WidgetState};
 use async_trait::async_trait;
 use std::time::Duration;
 
+/// Backlight brightness.
+///
+/// Format placeholders: `{icon}`, `{percent}`.
 #[derive(Debug)]
 pub struct Brightness {
     interval: Duration,
-    icon: String,
+    icons: IconPack,
+    format: Template,
 }
 
+const PLACEHOLDERS: &[&str] = &["icon", "percent"];
+
 #[async_trait]
 impl Widget for Brightness {
     fn interval(&self) -> Duration {
@@ -15,14 +21,16 @@ impl Widget for Brightness {
     }
 
     async fn get_output(&self) -> WidgetOutput {
+        let percent = (self.get_file_content().await / 7500_f64) * 100_f64;
         WidgetOutput {
-            text: format!(
-                "{} {:.0}",
-                self.icon,
-                (self.get_file_content().await / 7500_f64) * 100_f64
-            ),
+            text: self.format.render(|name| match name {
+                "icon" => self.icons.icon(Icon::Brightness, percent).into(),
+                _ => percent.into(),
+            }),
             use_default_foreground: true,
             use_default_background: true,
+            state: WidgetState::Normal,
+            metrics: vec![],
         }
     }
 }
@@ -31,10 +39,21 @@ impl Brightness {
     pub fn new(interval: Duration) -> Self {
         Self {
             interval,
-            icon: "\u{f185}".to_string(),
+            icons: IconPack::default(),
+            format: Template::parse("{icon} {percent}", PLACEHOLDERS).unwrap(),
         }
     }
 
+    pub fn icons(mut self, icons: IconPack) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
+    }
+
     pub async fn get_file_content(&self) -> f64 {
         let s = async_std::fs::read_to_string("/sys/class/backlight/intel_backlight/brightness")
             .await
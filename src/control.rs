@@ -0,0 +1,143 @@
+use crate::Handler;
+
+use async_std::channel::Sender;
+use async_std::io::BufReader;
+use async_std::os::unix::net::{UnixListener, UnixStream};
+use async_std::prelude::*;
+use smol::Task;
+
+use std::io::{self, BufRead, Write as _};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// `$XDG_RUNTIME_DIR/barr.sock`, falling back to the temp directory.
+pub fn default_socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("barr.sock")
+}
+
+/// Send `<widget> <command>` to a running barr and return its answer, as done
+/// by `barr msg date next`, e.g. from a dwm click handler.
+pub fn send_command(path: &Path, widget: &str, command: &str) -> io::Result<String> {
+    let mut stream = std::os::unix::net::UnixStream::connect(path)?;
+    writeln!(stream, "{} {}", widget, command)?;
+
+    let mut answer = String::new();
+    io::BufReader::new(stream).read_line(&mut answer)?;
+    Ok(answer.trim_end().to_string())
+}
+
+/// Accept commands on `path`, waking a widget through `wakers` to redraw it.
+pub(crate) async fn serve(path: PathBuf, widgets: Vec<Arc<Handler>>, wakers: Vec<Sender<()>>) {
+    if let Err(e) = remove_stale_socket(&path).await {
+        eprintln!("Failed to bind control socket {:?}: {}", path, e);
+        return;
+    }
+    let listener = match UnixListener::bind(&path).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to bind control socket {:?}: {}", path, e);
+            return;
+        }
+    };
+
+    let widgets = Arc::new(widgets);
+    let wakers = Arc::new(wakers);
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        if let Ok(stream) = stream {
+            let widgets = widgets.clone();
+            let wakers = wakers.clone();
+            Task::spawn(async move { respond(stream, &widgets, &wakers).await }).detach();
+        }
+    }
+}
+
+/// Remove a socket at `path` left behind by a previous run, which would make
+/// `bind` fail. A socket something still listens on and any other kind of file
+/// are left alone.
+pub(crate) async fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(()),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "file exists and is not a socket",
+        ));
+    }
+    if UnixStream::connect(path).await.is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "used by another barr",
+        ));
+    }
+    std::fs::remove_file(path)
+}
+
+/// Pass a single `<widget> <command>` line to every widget with that name and
+/// have the ones that handled it sampled right away.
+async fn respond(stream: UnixStream, widgets: &[Arc<Handler>], wakers: &[Sender<()>]) {
+    let mut line = String::new();
+    if BufReader::new(&stream).read_line(&mut line).await.is_err() {
+        return;
+    }
+
+    let mut words = line.split_whitespace();
+    let answer = match (words.next(), words.next()) {
+        (Some(name), Some(command)) => {
+            let mut answer = format!("error: no widget named `{}`", name);
+            for (i, widget) in widgets.iter().enumerate() {
+                if widget.name() != name {
+                    continue;
+                }
+                if widget.on_command(command) {
+                    // Full while a wake-up is already pending
+                    let _ = wakers[i].try_send(());
+                    answer = "ok".to_string();
+                } else {
+                    answer = format!("error: `{}` doesn't understand `{}`", name, command);
+                }
+            }
+            answer
+        }
+        _ => "error: expected `<widget> <command>`".to_string(),
+    };
+
+    let _ = (&stream)
+        .write_all(format!("{}\n", answer).as_bytes())
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_only_stale_sockets() {
+        let dir = std::env::temp_dir().join(format!("barr-control-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // A mistyped path must not delete an unrelated file
+        let file = dir.join("notes.txt");
+        std::fs::write(&file, "keep").unwrap();
+        assert!(smol::run(remove_stale_socket(&file)).is_err());
+        assert!(file.exists());
+
+        let socket = dir.join("barr.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        assert!(smol::run(remove_stale_socket(&socket)).is_err());
+        assert!(socket.exists());
+
+        drop(listener);
+        smol::run(remove_stale_socket(&socket)).unwrap();
+        assert!(!socket.exists());
+        smol::run(remove_stale_socket(&socket)).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
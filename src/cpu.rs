@@ -1,16 +1,26 @@
-use crate::{Widget, WidgetOutput};
+use crate::{
+    Icon, IconPack, Metric, Scale, Sparkline, Template, TemplateError, Thresholds, Widget,
+    WidgetOutput, WidgetState,
+};
 use async_trait::async_trait;
 use psutil::cpu::CpuPercentCollector;
 use std::sync::Mutex;
 use std::time::Duration;
 
+/// Total CPU usage.
+///
+/// Format placeholders: `{icon}`, `{percent}`, `{sparkline}` (recent usage).
 pub struct CPU {
     interval: Duration,
     collector: Mutex<CpuPercentCollector>,
-    tresholds: Vec<(f32, String)>,
-    icon: String,
+    thresholds: Thresholds,
+    icons: IconPack,
+    format: Template,
+    sparkline: Sparkline,
 }
 
+const PLACEHOLDERS: &[&str] = &["icon", "percent", "sparkline"];
+
 #[async_trait]
 impl Widget for CPU {
     fn interval(&self) -> Duration {
@@ -19,29 +29,30 @@ impl Widget for CPU {
 
     async fn get_output(&self) -> WidgetOutput {
         let cpu = self.collector.lock().unwrap().cpu_percent().unwrap();
-        let mut text = format!("{} {:2.0}", self.icon, cpu);
+        self.sparkline.push(f64::from(cpu));
+
+        let mut text = self.format.render(|name| match name {
+            "icon" => self.icons.icon(Icon::Cpu, f64::from(cpu)).into(),
+            "sparkline" => self.sparkline.render().into(),
+            _ => f64::from(cpu).into(),
+        });
 
         let mut use_default_fg = true;
 
-        let fg = {
-            let mut fg = None;
-            for (treshold, color) in self.tresholds.iter().rev() {
-                if cpu >= *treshold {
-                    fg = Some(color);
-                    break;
-                }
-            }
-            fg
-        };
-
-        if let Some(fg) = fg {
+        if let Some(fg) = self.thresholds.color(f64::from(cpu)) {
             text = format!("<span foreground='{}'>{}</span>", fg, text);
-            use_default_fg = true;
+            use_default_fg = false;
         }
         WidgetOutput {
             text,
             use_default_foreground: use_default_fg,
             use_default_background: true,
+            state: WidgetState::Normal,
+            metrics: vec![Metric::gauge(
+                "cpu_usage_percent",
+                "Total CPU usage in percent",
+                f64::from(cpu),
+            )],
         }
     }
 }
@@ -51,12 +62,35 @@ impl CPU {
         Self {
             interval,
             collector: Mutex::new(CpuPercentCollector::new().unwrap()),
-            tresholds: vec![
-                (35_f32, "#E9A072".to_string()),
-                (50_f32, "#F2665F".to_string()),
-                (80_f32, "#FF0000".to_string()),
-            ],
-            icon: "\u{f0e4}".to_string(),
+            thresholds: Thresholds::ascending(&[
+                (35_f64, "#E9A072"),
+                (50_f64, "#F2665F"),
+                (80_f64, "#FF0000"),
+            ]),
+            icons: IconPack::default(),
+            format: Template::parse("{icon} {percent:2}", PLACEHOLDERS).unwrap(),
+            sparkline: Sparkline::new(10, Scale::Fixed(0.0, 100.0)),
         }
     }
+
+    pub fn thresholds(mut self, thresholds: Thresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Keep the last `length` samples for `{sparkline}`.
+    pub fn sparkline(mut self, length: usize, scale: Scale) -> Self {
+        self.sparkline = Sparkline::new(length, scale);
+        self
+    }
+
+    pub fn icons(mut self, icons: IconPack) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
+    }
 }
@@ -1,35 +1,330 @@
-use crate::{Widget, WidgetOutput};
+use crate::{TemplateError, Widget, WidgetOutput, WidgetState};
 use async_trait::async_trait;
+use chrono::format::{Item, StrftimeItems};
 use chrono::prelude::*;
+use chrono::Locale;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 
-#[derive(Debug, Copy, Clone)]
+/// Current local time, optionally followed by a world clock and countdowns.
+///
+/// The formats are `strftime` patterns, see `chrono::format::strftime` for the
+/// supported specifiers, e.g. `%V` for the ISO week or `%j` for the day of the
+/// year. Month and day names follow `locale`. Every zone added with
+/// `timezone` is appended as e.g. `| Jakarta 14:02`, every countdown as
+/// `| standup in 12m`.
+///
+/// Named `date` on the control socket, `next`, `previous` and `reset` cycle
+/// through the formats added with `alternate_format`.
+#[derive(Debug)]
 pub struct Date {
     interval: Duration,
+    formats: Vec<String>,
+    current: AtomicUsize,
+    locale: Locale,
+    zones: Vec<(String, tz::TimeZone)>,
+    zone_format: String,
+    countdowns: Vec<Countdown>,
+    urgent_color: String,
+}
+
+#[derive(Debug, Clone)]
+enum Target {
+    At(DateTime<Local>),
+    Daily(NaiveTime),
+}
+
+#[derive(Debug, Clone)]
+struct Countdown {
+    label: String,
+    target: Target,
+    urgent: Duration,
 }
 
 #[async_trait]
 impl Widget for Date {
     async fn get_output(&self) -> WidgetOutput {
+        let now = Utc::now();
+        let mut text = self.get_date(now);
+
+        let mut urgent = false;
+        for countdown in &self.countdowns {
+            let left = match countdown.time_left(now.with_timezone(&Local)) {
+                Some(left) => left,
+                None => continue,
+            };
+            let entry = format!("{} in {}", countdown.label, format_left(left));
+            if left <= countdown.urgent {
+                urgent = true;
+                text.push_str(&format!(
+                    " | <span foreground='{}'><b>{}</b></span>",
+                    self.urgent_color, entry
+                ));
+            } else {
+                text.push_str(&format!(" | {}", entry));
+            }
+        }
+
         WidgetOutput {
-            text: Self::get_date("%a, %d %b %H:%M:%S"),
+            text,
             use_default_foreground: true,
             use_default_background: true,
+            state: if urgent {
+                WidgetState::State("urgent")
+            } else {
+                WidgetState::Normal
+            },
+            metrics: vec![],
         }
     }
 
     fn interval(&self) -> Duration {
         self.interval
     }
+
+    fn name(&self) -> &str {
+        "date"
+    }
+
+    fn on_command(&self, command: &str) -> bool {
+        let count = self.formats.len();
+        let current = self.current.load(Ordering::Relaxed);
+        let next = match command {
+            "next" => (current + 1) % count,
+            "previous" => (current + count - 1) % count,
+            "reset" => 0,
+            _ => return false,
+        };
+        self.current.store(next, Ordering::Relaxed);
+        true
+    }
 }
 
 impl Date {
     pub fn new(interval: Duration) -> Self {
-        Self { interval }
+        Self {
+            interval,
+            formats: vec!["%a, %d %b %H:%M:%S".to_string()],
+            current: AtomicUsize::new(0),
+            locale: Locale::POSIX,
+            zones: vec![],
+            zone_format: "%H:%M".to_string(),
+            countdowns: vec![],
+            urgent_color: "#FF0000".to_string(),
+        }
+    }
+
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.formats[0] = check_format(format)?;
+        Ok(self)
+    }
+
+    /// Add a format to cycle to over the control socket, e.g. `week %V`.
+    pub fn alternate_format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.formats.push(check_format(format)?);
+        Ok(self)
+    }
+
+    /// Locale for month and day names, e.g. `"de_DE".parse().unwrap()`.
+    pub fn locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Show the time in `name`, e.g. `Asia/Jakarta`, labelled with `label`.
+    ///
+    /// The zone is read from the system tzdata, usually
+    /// `/usr/share/zoneinfo`.
+    pub fn timezone(mut self, label: &str, name: &str) -> io::Result<Self> {
+        let zone = tz::TimeZone::from_posix_tz(name).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("timezone \"{}\": {}", name, e),
+            )
+        })?;
+        self.zones.push((label.to_string(), zone));
+        Ok(self)
+    }
+
+    /// Format of the times in the world clock, `%H:%M` by default.
+    pub fn zone_format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.zone_format = check_format(format)?;
+        Ok(self)
+    }
+
+    /// Count down to `at`, shown in the urgent color once less than `urgent`
+    /// is left and hidden once it has passed.
+    pub fn countdown(mut self, label: &str, at: DateTime<Local>, urgent: Duration) -> Self {
+        self.countdowns.push(Countdown {
+            label: label.to_string(),
+            target: Target::At(at),
+            urgent,
+        });
+        self
+    }
+
+    /// Count down to `at` every day, e.g. a standup meeting.
+    pub fn daily_countdown(mut self, label: &str, at: NaiveTime, urgent: Duration) -> Self {
+        self.countdowns.push(Countdown {
+            label: label.to_string(),
+            target: Target::Daily(at),
+            urgent,
+        });
+        self
+    }
+
+    pub fn urgent_color(mut self, color: &str) -> Self {
+        self.urgent_color = color.to_string();
+        self
+    }
+
+    fn get_date(&self, now: DateTime<Utc>) -> String {
+        let format = &self.formats[self.current.load(Ordering::Relaxed)];
+        let mut text = now
+            .with_timezone(&Local)
+            .format_localized(format, self.locale)
+            .to_string();
+
+        for (label, zone) in &self.zones {
+            let offset = zone
+                .find_local_time_type(now.timestamp())
+                .ok()
+                .and_then(|t| FixedOffset::east_opt(t.ut_offset()));
+            if let Some(offset) = offset {
+                let time = now
+                    .with_timezone(&offset)
+                    .format_localized(&self.zone_format, self.locale);
+                text.push_str(&format!(" | {} {}", label, time));
+            }
+        }
+
+        text
+    }
+}
+
+impl Countdown {
+    fn time_left(&self, now: DateTime<Local>) -> Option<Duration> {
+        let target = match self.target {
+            Target::At(at) => at,
+            Target::Daily(time) => {
+                let today = now.naive_local().date().and_time(time);
+                let next = if today > now.naive_local() {
+                    today
+                } else {
+                    today + chrono::Duration::days(1)
+                };
+                Local.from_local_datetime(&next).earliest()?
+            }
+        };
+        (target - now).to_std().ok()
+    }
+}
+
+/// The two largest units of `left`, e.g. `3d 4h` or `12m`.
+fn format_left(left: Duration) -> String {
+    let minutes = left.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / (60 * 24), minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+fn check_format(format: &str) -> Result<String, TemplateError> {
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(TemplateError(format!(
+            "invalid strftime format \"{}\"",
+            format
+        )));
+    }
+    Ok(format.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(day: u32, hour: u32, min: u32) -> DateTime<Local> {
+        let time = NaiveDate::from_ymd_opt(2020, 6, day)
+            .and_then(|date| date.and_hms_opt(hour, min, 0))
+            .unwrap();
+        Local.from_local_datetime(&time).earliest().unwrap()
+    }
+
+    fn countdown(target: Target) -> Countdown {
+        Countdown {
+            label: "standup".to_string(),
+            target,
+            urgent: Duration::from_secs(5 * 60),
+        }
+    }
+
+    #[test]
+    fn cycles_through_formats() {
+        let date = Date::new(Duration::from_secs(1))
+            .alternate_format("%H:%M")
+            .and_then(|date| date.alternate_format("week %V"))
+            .unwrap();
+        let current = || date.current.load(Ordering::Relaxed);
+
+        assert!(date.on_command("previous"));
+        assert_eq!(current(), 2);
+        assert!(date.on_command("next"));
+        assert_eq!(current(), 0);
+        assert!(date.on_command("next"));
+        assert!(date.on_command("reset"));
+        assert_eq!(current(), 0);
+        assert!(!date.on_command("bogus"));
+
+        // A single format stays put
+        let date = Date::new(Duration::from_secs(1));
+        assert!(date.on_command("next"));
+        assert_eq!(date.current.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn daily_rolls_over_to_tomorrow() {
+        let standup = countdown(Target::Daily(NaiveTime::from_hms_opt(9, 30, 0).unwrap()));
+        assert_eq!(
+            standup.time_left(local(1, 9, 0)),
+            Some(Duration::from_secs(30 * 60))
+        );
+        // Right at the time the next one is a day away
+        assert_eq!(
+            standup.time_left(local(1, 9, 30)),
+            Some(Duration::from_secs(24 * 60 * 60))
+        );
+        assert_eq!(
+            standup.time_left(local(1, 10, 0)),
+            Some(Duration::from_secs(23 * 60 * 60 + 30 * 60))
+        );
+    }
+
+    #[test]
+    fn at_is_hidden_after_the_target() {
+        let release = countdown(Target::At(local(3, 12, 0)));
+        assert_eq!(
+            release.time_left(local(1, 12, 0)),
+            Some(Duration::from_secs(2 * 24 * 60 * 60))
+        );
+        assert_eq!(release.time_left(local(3, 12, 1)), None);
     }
 
-    fn get_date(fmt: &str) -> String {
-        let now = Local::now();
-        now.format(fmt).to_string()
+    #[test]
+    fn two_largest_units() {
+        let left = |days: u64, hours: u64, minutes: u64| {
+            format_left(Duration::from_secs(
+                ((days * 24 + hours) * 60 + minutes) * 60 + 59,
+            ))
+        };
+        assert_eq!(left(3, 4, 12), "3d 4h");
+        assert_eq!(left(3, 0, 12), "3d 0h");
+        assert_eq!(left(0, 4, 12), "4h 12m");
+        assert_eq!(left(0, 0, 12), "12m");
+        assert_eq!(left(0, 0, 0), "0m");
     }
 }
@@ -0,0 +1,366 @@
+use crate::Metric;
+
+use async_std::channel::{self, Receiver, Sender};
+use chrono::{NaiveDate, TimeZone, Utc};
+use smol::{Task, Timer};
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often old files are removed
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How long samples are collected before they are written at once
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// Samples waiting to be written, more are dropped
+const QUEUE_SIZE: usize = 1024;
+
+/// Rolling CSV log of every metric reported by the widgets.
+///
+/// Rows go to one file per day in the `path` directory, named after the UTC
+/// date such as `2020-06-30.csv`. Each row is `timestamp,name,labels,value`
+/// where `timestamp` is in unix seconds and `labels` is a `;` separated list
+/// of `key=value` pairs. Files entirely older than `retention` are removed
+/// once an hour.
+///
+/// Samples are written in batches by a background task, so a slow disk never
+/// holds up the bars.
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    retention: Duration,
+    last_prune: Option<Instant>,
+}
+
+/// Hands samples over to the task writing the history.
+pub(crate) struct Recorder {
+    sender: Sender<(i64, Vec<Metric>)>,
+}
+
+impl History {
+    pub fn new(path: impl Into<PathBuf>, retention: Duration) -> Self {
+        Self {
+            path: path.into(),
+            retention,
+            last_prune: None,
+        }
+    }
+
+    /// `$XDG_DATA_HOME/barr/history`, falling back to `~/.local/share`.
+    pub fn default_path() -> PathBuf {
+        data_dir().join("history")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Start writing samples in the background.
+    pub(crate) fn spawn(self) -> Recorder {
+        let (sender, receiver) = channel::bounded(QUEUE_SIZE);
+        Task::spawn(self.serve(receiver)).detach();
+        Recorder { sender }
+    }
+
+    async fn serve(mut self, receiver: Receiver<(i64, Vec<Metric>)>) {
+        while let Ok(sample) = receiver.recv().await {
+            Timer::after(FLUSH_INTERVAL).await;
+
+            let mut batch = vec![sample];
+            while let Ok(sample) = receiver.try_recv() {
+                batch.push(sample);
+            }
+
+            let (history, result) = Task::blocking(async move {
+                let result = self.write(&batch);
+                (self, result)
+            })
+            .await;
+            self = history;
+
+            if let Err(e) = result {
+                eprintln!("Failed to record history to {:?}: {}", self.path, e);
+            }
+        }
+    }
+
+    fn write(&mut self, batch: &[(i64, Vec<Metric>)]) -> io::Result<()> {
+        fs::create_dir_all(&self.path)?;
+
+        // A batch spans two files right after midnight
+        let mut files: Vec<(PathBuf, String)> = vec![];
+        for (timestamp, metrics) in batch {
+            let path = self.file(day(*timestamp));
+            let rows = match files.iter_mut().find(|(p, _)| *p == path) {
+                Some((_, rows)) => rows,
+                None => {
+                    files.push((path, String::new()));
+                    &mut files.last_mut().unwrap().1
+                }
+            };
+
+            for metric in metrics {
+                let labels: Vec<String> = metric
+                    .labels
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k, v.replace([',', ';'], "_")))
+                    .collect();
+                rows.push_str(&format!(
+                    "{},{},{},{}\n",
+                    timestamp,
+                    metric.name,
+                    labels.join(";"),
+                    metric.value
+                ));
+            }
+        }
+
+        for (path, rows) in files {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?
+                .write_all(rows.as_bytes())?;
+        }
+
+        let prune_due = match self.last_prune {
+            Some(last) => last.elapsed() >= PRUNE_INTERVAL,
+            None => true,
+        };
+        if prune_due {
+            self.last_prune = Some(Instant::now());
+            self.prune()?;
+        }
+        Ok(())
+    }
+
+    /// Summarize every series of `widget` (matched as the metric name prefix,
+    /// e.g. `cpu` or `network`) recorded in the last `since`.
+    #[allow(clippy::cast_possible_wrap, clippy::cast_precision_loss)]
+    pub fn summarize(&self, widget: &str, since: Duration) -> io::Result<Vec<Summary>> {
+        let oldest = Utc::now().timestamp() - since.as_secs() as i64;
+
+        let mut rows = vec![];
+        for (_, path) in self.files_since(oldest)? {
+            rows.extend(read_rows(&path)?);
+        }
+
+        let mut summaries: Vec<Summary> = vec![];
+        for row in rows {
+            if row.timestamp < oldest || !row.name.starts_with(widget) {
+                continue;
+            }
+
+            let series = if row.labels.is_empty() {
+                row.name
+            } else {
+                format!("{}{{{}}}", row.name, row.labels)
+            };
+
+            match summaries.iter_mut().find(|s| s.series == series) {
+                Some(s) => {
+                    s.count += 1;
+                    s.min = s.min.min(row.value);
+                    s.max = s.max.max(row.value);
+                    s.avg += (row.value - s.avg) / s.count as f64;
+                    s.last = row.value;
+                }
+                None => summaries.push(Summary {
+                    series,
+                    count: 1,
+                    min: row.value,
+                    max: row.value,
+                    avg: row.value,
+                    last: row.value,
+                }),
+            }
+        }
+        Ok(summaries)
+    }
+
+    /// Remove the files of the days that ended before the retention.
+    #[allow(clippy::cast_possible_wrap)]
+    fn prune(&self) -> io::Result<()> {
+        let oldest = Utc::now().timestamp() - self.retention.as_secs() as i64;
+        let first_kept = day(oldest);
+
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if matches!(day_of(&path), Some(day) if day < first_kept) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn file(&self, day: NaiveDate) -> PathBuf {
+        self.path.join(format!("{}.csv", day.format("%Y-%m-%d")))
+    }
+
+    /// Files holding rows from `oldest` on, sorted by day.
+    fn files_since(&self, oldest: i64) -> io::Result<Vec<(NaiveDate, PathBuf)>> {
+        let first = day(oldest);
+
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        let mut files = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if let Some(day) = day_of(&path) {
+                if day >= first {
+                    files.push((day, path));
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+impl Recorder {
+    /// Queue `metrics` sampled now, dropped if the writer has fallen behind.
+    pub(crate) fn record(&self, metrics: &[Metric]) {
+        if !metrics.is_empty() {
+            let _ = self
+                .sender
+                .try_send((Utc::now().timestamp(), metrics.to_vec()));
+        }
+    }
+}
+
+/// UTC day of a unix timestamp.
+fn day(timestamp: i64) -> NaiveDate {
+    Utc.timestamp_opt(timestamp, 0).unwrap().naive_utc().date()
+}
+
+/// Day of a `2020-06-30.csv` history file.
+fn day_of(path: &Path) -> Option<NaiveDate> {
+    if path.extension()? != "csv" {
+        return None;
+    }
+    NaiveDate::parse_from_str(path.file_stem()?.to_str()?, "%Y-%m-%d").ok()
+}
+
+/// Statistics of one recorded series, as printed by `barr history`.
+#[derive(Debug)]
+pub struct Summary {
+    pub series: String,
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub last: f64,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: min {:.2}  avg {:.2}  max {:.2}  last {:.2}  ({} samples)",
+            self.series, self.min, self.avg, self.max, self.last, self.count
+        )
+    }
+}
+
+/// Parse durations such as `90s`, `15m`, `1h` or `7d`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let unit_at = s.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = s.split_at(unit_at);
+    let value: u64 = value.parse().ok()?;
+
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return None,
+    };
+    Some(Duration::from_secs(value * secs))
+}
+
+pub(crate) fn data_dir() -> PathBuf {
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("barr")
+}
+
+struct Row {
+    timestamp: i64,
+    name: String,
+    labels: String,
+    value: f64,
+}
+
+/// Read every well-formed row, a missing file is an empty history.
+fn read_rows(path: &Path) -> io::Result<Vec<Row>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+
+    let mut rows = vec![];
+    for line in BufReader::new(file).lines() {
+        rows.extend(parse_row(&line?));
+    }
+    Ok(rows)
+}
+
+fn parse_row(line: &str) -> Option<Row> {
+    let mut fields = line.splitn(4, ',');
+    let timestamp = fields.next()?.parse().ok()?;
+    let name = fields.next()?.to_string();
+    let labels = fields.next()?.to_string();
+    let value = fields.next()?.parse().ok()?;
+
+    Some(Row {
+        timestamp,
+        name,
+        labels,
+        value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_per_day_and_prunes_old_days() {
+        let dir = std::env::temp_dir().join(format!("barr-history-{}", std::process::id()));
+        let mut history = History::new(&dir, Duration::from_secs(60 * 60 * 24 * 2));
+
+        let now = Utc::now().timestamp();
+        let day_secs = 60 * 60 * 24;
+        let cpu = || vec![Metric::gauge("cpu_usage_percent", "", 12.0)];
+        history
+            .write(&[(now - 5 * day_secs, cpu()), (now - 60, cpu()), (now, cpu())])
+            .unwrap();
+
+        let days: Vec<NaiveDate> = history
+            .files_since(0)
+            .unwrap()
+            .into_iter()
+            .map(|(day, _)| day)
+            .collect();
+        assert!(!days.contains(&day(now - 5 * day_secs)));
+        assert_eq!(days.last(), Some(&day(now)));
+
+        let summaries = history
+            .summarize("cpu", Duration::from_secs(60 * 60))
+            .unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].count, 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
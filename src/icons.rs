@@ -0,0 +1,159 @@
+#![allow(clippy::non_ascii_literal)]
+
+/// Set of glyphs used by the widgets, pick the one your bar font supports.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum IconPack {
+    /// Font Awesome 4 codepoints, also included in Nerd Fonts
+    #[default]
+    FontAwesome,
+    /// Material Design glyphs of Nerd Fonts v3, with finer level ramps
+    NerdFont,
+    Emoji,
+    Ascii,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Icon {
+    /// Level is the volume in percent, 0 when muted
+    Volume,
+    Brightness,
+    Cpu,
+    Memory,
+    Download,
+    Upload,
+    /// Level is the state of charge in percent
+    Battery,
+    Charging,
+    /// On AC power with the battery full
+    Plugged,
+    /// Level is the signal quality in percent
+    Wifi,
+    Vpn,
+}
+
+impl IconPack {
+    /// Glyph for `icon`, `level` (0 to 100) picks a step for icons with one.
+    pub fn icon(self, icon: Icon, level: f64) -> &'static str {
+        match icon {
+            Icon::Volume => {
+                let [off, low, high] = self.volume();
+                if level <= 0.0 {
+                    off
+                } else if level < 50.0 {
+                    low
+                } else {
+                    high
+                }
+            }
+            Icon::Battery => ramp(self.battery(), level),
+            Icon::Wifi => ramp(self.wifi(), level),
+            icon => self.fixed(icon),
+        }
+    }
+
+    fn fixed(self, icon: Icon) -> &'static str {
+        match self {
+            IconPack::FontAwesome => match icon {
+                Icon::Brightness => "\u{f185}",
+                Icon::Cpu => "\u{f0e4}",
+                Icon::Memory => "\u{f2db}",
+                Icon::Download => "\u{f0ed}",
+                Icon::Upload => "\u{f0ee}",
+                Icon::Vpn => "\u{f023}",
+                _ => "\u{f0e7}",
+            },
+            IconPack::NerdFont => match icon {
+                Icon::Brightness => "\u{f00e0}",
+                Icon::Cpu => "\u{f0ee0}",
+                Icon::Memory => "\u{f035b}",
+                Icon::Download => "\u{f01da}",
+                Icon::Upload => "\u{f0552}",
+                Icon::Charging => "\u{f0084}",
+                Icon::Vpn => "\u{f0582}",
+                _ => "\u{f06a5}",
+            },
+            IconPack::Emoji => match icon {
+                Icon::Brightness => "🔆",
+                Icon::Cpu => "🖥",
+                Icon::Memory => "🧠",
+                Icon::Download => "⬇",
+                Icon::Upload => "⬆",
+                Icon::Charging => "⚡",
+                Icon::Vpn => "🔒",
+                _ => "🔌",
+            },
+            IconPack::Ascii => match icon {
+                Icon::Brightness => "bri",
+                Icon::Cpu => "cpu",
+                Icon::Memory => "mem",
+                Icon::Download => "rx",
+                Icon::Upload => "tx",
+                Icon::Charging => "chr",
+                Icon::Vpn => "vpn",
+                _ => "ac",
+            },
+        }
+    }
+
+    fn volume(self) -> [&'static str; 3] {
+        match self {
+            IconPack::FontAwesome => ["\u{f026}", "\u{f027}", "\u{f028}"],
+            IconPack::NerdFont => ["\u{f0581}", "\u{f057f}", "\u{f057e}"],
+            IconPack::Emoji => ["🔇", "🔉", "🔊"],
+            IconPack::Ascii => ["mute", "vol", "VOL"],
+        }
+    }
+
+    fn battery(self) -> &'static [&'static str] {
+        match self {
+            IconPack::FontAwesome => &["\u{f244}", "\u{f243}", "\u{f242}", "\u{f241}", "\u{f240}"],
+            IconPack::NerdFont => &[
+                "\u{f008e}",
+                "\u{f007a}",
+                "\u{f007b}",
+                "\u{f007c}",
+                "\u{f007d}",
+                "\u{f007e}",
+                "\u{f007f}",
+                "\u{f0080}",
+                "\u{f0081}",
+                "\u{f0079}",
+            ],
+            IconPack::Emoji => &["🪫", "🔋", "🔋", "🔋", "🔋"],
+            IconPack::Ascii => &["[    ]", "[=   ]", "[==  ]", "[=== ]", "[====]"],
+        }
+    }
+
+    fn wifi(self) -> &'static [&'static str] {
+        match self {
+            // Font Awesome 4 has a single wifi glyph
+            IconPack::FontAwesome => &["\u{f1eb}"],
+            IconPack::NerdFont => &[
+                "\u{f092f}",
+                "\u{f091f}",
+                "\u{f0922}",
+                "\u{f0925}",
+                "\u{f0928}",
+            ],
+            IconPack::Emoji => &["📶"],
+            IconPack::Ascii => &[
+                "wifi:    ",
+                "wifi:.   ",
+                "wifi:..  ",
+                "wifi:... ",
+                "wifi:....",
+            ],
+        }
+    }
+}
+
+/// Split 0 to 100 in as many equal steps as there are icons.
+#[allow(
+    clippy::cast_sign_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss
+)]
+fn ramp(icons: &'static [&'static str], level: f64) -> &'static str {
+    let step = (level.max(0.0) / 100.0 * icons.len() as f64) as usize;
+    icons[step.min(icons.len() - 1)]
+}
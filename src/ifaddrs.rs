@@ -0,0 +1,45 @@
+use std::ffi::CStr;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Every IPv4 and IPv6 address of every interface, from `getifaddrs(3)`.
+#[allow(clippy::cast_ptr_alignment)]
+pub(crate) fn addresses() -> io::Result<Vec<(String, IpAddr)>> {
+    let mut head: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: on success `head` points to a list we free below
+    if unsafe { libc::getifaddrs(&mut head) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut addresses = vec![];
+    let mut current = head;
+    while !current.is_null() {
+        // SAFETY: every entry and its fields stay valid until `freeifaddrs`,
+        // `ifa_addr` is a `sockaddr_in` or `sockaddr_in6` as told by the family
+        unsafe {
+            let ifa = &*current;
+            current = ifa.ifa_next;
+            if ifa.ifa_addr.is_null() {
+                continue;
+            }
+
+            let address = match i32::from((*ifa.ifa_addr).sa_family) {
+                libc::AF_INET => {
+                    let sin = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                    IpAddr::V4(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)))
+                }
+                libc::AF_INET6 => {
+                    let sin6 = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                    IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr))
+                }
+                _ => continue,
+            };
+            let name = CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned();
+            addresses.push((name, address));
+        }
+    }
+
+    // SAFETY: `head` came from a successful `getifaddrs`
+    unsafe { libc::freeifaddrs(head) };
+    Ok(addresses)
+}
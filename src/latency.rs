@@ -0,0 +1,249 @@
+#![allow(clippy::non_ascii_literal)]
+
+use crate::route;
+use crate::{
+    Metric, Template, TemplateError, Thresholds, Value, Widget, WidgetOutput, WidgetState,
+};
+use async_std::future;
+use async_std::net::{TcpStream, ToSocketAddrs};
+use async_trait::async_trait;
+use futures::future::join_all;
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Round trip time to a list of hosts, measured by timing TCP connects.
+///
+/// Hosts are `host:port` pairs, the gateway of the default route on port 53
+/// if none is given. For the gateway a refused connection still counts as a
+/// round trip, so it doesn't need to run a DNS server, while for the given
+/// hosts it counts as a failure.
+///
+/// Format placeholders: `{latency}` (median of the recent round trips in ms),
+/// `{min}`, `{max}`. After `offline_after` rounds in a row without any answer
+/// it shows `offline` in the `offline` state.
+pub struct Latency {
+    interval: Duration,
+    hosts: Vec<String>,
+    timeout: Duration,
+    window: usize,
+    offline_after: usize,
+    thresholds: Thresholds,
+    format: Template,
+
+    samples: Mutex<VecDeque<f64>>,
+    failures: Mutex<usize>,
+}
+
+const PLACEHOLDERS: &[&str] = &["latency", "min", "max"];
+
+#[async_trait]
+impl Widget for Latency {
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn get_output(&self) -> WidgetOutput {
+        let (hosts, refused_answers) = if self.hosts.is_empty() {
+            match route::default_route() {
+                Ok(Some(route)) => (
+                    vec![SocketAddr::from((route.gateway, 53)).to_string()],
+                    true,
+                ),
+                _ => (vec![], true),
+            }
+        } else {
+            (self.hosts.clone(), false)
+        };
+
+        let round_trips = join_all(
+            hosts
+                .iter()
+                .map(|host| measure(host, self.timeout, refused_answers)),
+        )
+        .await;
+        let metrics = hosts
+            .iter()
+            .zip(&round_trips)
+            .filter_map(|(host, round_trip)| {
+                let ms = round_trip.as_ref()?.as_secs_f64() * 1000.0;
+                Some(
+                    Metric::gauge("latency_ms", "TCP connect round trip in ms", ms)
+                        .label("host", host.as_str()),
+                )
+            })
+            .collect();
+
+        let mut samples = self.samples.lock().unwrap();
+        let mut failures = self.failures.lock().unwrap();
+        if round_trips.iter().any(Option::is_some) {
+            *failures = 0;
+        } else {
+            *failures += 1;
+        }
+        for round_trip in round_trips.into_iter().flatten() {
+            if samples.len() == self.window {
+                samples.pop_front();
+            }
+            samples.push_back(round_trip.as_secs_f64() * 1000.0);
+        }
+
+        if *failures >= self.offline_after {
+            samples.clear();
+            return WidgetOutput {
+                text: "<span foreground='red'>offline</span>".to_string(),
+                use_default_foreground: false,
+                use_default_background: true,
+                state: WidgetState::State("offline"),
+                metrics,
+            };
+        }
+
+        if samples.is_empty() {
+            return WidgetOutput {
+                text: "<span foreground='grey'>…</span>".to_string(),
+                use_default_foreground: true,
+                use_default_background: true,
+                state: WidgetState::Normal,
+                metrics,
+            };
+        }
+
+        let mut sorted: Vec<f64> = samples.iter().copied().collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        // The middle value, or the mean of the two middle ones
+        let n = sorted.len();
+        let median = (sorted[(n - 1) / 2] + sorted[n / 2]) / 2.0;
+
+        let mut text = self.format.render(|name| match name {
+            "min" => Value::Number(sorted[0], 1),
+            "max" => Value::Number(sorted[n - 1], 1),
+            _ => Value::Number(median, 1),
+        });
+
+        let mut use_default_fg = true;
+        if let Some(fg) = self.thresholds.color(median) {
+            text = format!("<span foreground='{}'>{}</span>", fg, text);
+            use_default_fg = false;
+        }
+
+        WidgetOutput {
+            text,
+            use_default_foreground: use_default_fg,
+            use_default_background: true,
+            state: WidgetState::Normal,
+            metrics,
+        }
+    }
+}
+
+impl Latency {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            hosts: vec![],
+            timeout: Duration::from_secs(2),
+            window: 5,
+            offline_after: 3,
+            thresholds: Thresholds::ascending(&[(100_f64, "#E9A072"), (300_f64, "#F2665F")]),
+            format: Template::parse("{latency} ms", PLACEHOLDERS).unwrap(),
+            samples: Mutex::new(VecDeque::new()),
+            failures: Mutex::new(0),
+        }
+    }
+
+    /// Add a `host:port` to measure, e.g. `1.1.1.1:53` or `example.com:443`.
+    pub fn host(mut self, host: &str) -> Self {
+        self.hosts.push(host.to_string());
+        self
+    }
+
+    /// Give up on a connect after `timeout`, 2 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Number of recent round trips the median is taken over.
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    /// Rounds without any answer before showing `offline`.
+    pub fn offline_after(mut self, rounds: usize) -> Self {
+        self.offline_after = rounds.max(1);
+        self
+    }
+
+    pub fn thresholds(mut self, thresholds: Thresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
+    }
+}
+
+/// Time until `host` accepts a TCP connection, or refuses it with
+/// `refused_answers`, `None` on timeout or any other error. Name resolution
+/// isn't part of the measurement.
+async fn measure(host: &str, timeout: Duration, refused_answers: bool) -> Option<Duration> {
+    let addr = future::timeout(timeout, host.to_socket_addrs())
+        .await
+        .ok()?
+        .ok()?
+        .next()?;
+
+    let start = Instant::now();
+    match future::timeout(timeout, TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => Some(start.elapsed()),
+        Ok(Err(e)) if refused_answers && e.kind() == io::ErrorKind::ConnectionRefused => {
+            Some(start.elapsed())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn measures_local_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let latency =
+            Latency::new(Duration::from_secs(1)).host(&listener.local_addr().unwrap().to_string());
+
+        let output = smol::run(latency.get_output());
+        let median: f64 = output.text.trim_end_matches(" ms").parse().unwrap();
+        assert!(median.is_finite());
+        assert_eq!(output.state, WidgetState::Normal);
+        assert_eq!(output.metrics.len(), 1);
+    }
+
+    #[test]
+    fn offline_after_failures() {
+        // Nothing listens once the listener is dropped
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let latency = Latency::new(Duration::from_secs(1))
+            .host(&addr.to_string())
+            .offline_after(3);
+
+        for _ in 0..2 {
+            let output = smol::run(latency.get_output());
+            assert_eq!(output.state, WidgetState::Normal);
+        }
+        let output = smol::run(latency.get_output());
+        assert_eq!(output.state, WidgetState::State("offline"));
+        assert!(output.text.contains("offline"));
+    }
+}
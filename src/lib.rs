@@ -1,35 +1,77 @@
-use async_std::channel;
+use async_std::{channel, future};
 use async_trait::async_trait;
 use smol::{Task, Timer};
-use std::process::Command;
 
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 mod alsa;
+mod bar;
 mod battery;
 mod brightness;
+mod control;
 mod cpu;
 mod date;
+mod history;
+mod icons;
+mod ifaddrs;
+mod latency;
+mod markup;
 mod memory;
+mod metrics;
 mod mpd;
 mod network;
+mod nl80211;
+mod route;
+mod sink;
+mod sparkline;
+mod template;
+mod thresholds;
+mod units;
+mod usage;
+mod vpn;
 mod wifi;
 
 pub use crate::alsa::Alsa;
+pub use crate::bar::{Align, Bar, HideWhen, Slot, WidgetId};
 pub use crate::battery::Battery;
 pub use crate::brightness::Brightness;
+pub use crate::control::{default_socket_path, send_command};
 pub use crate::cpu::CPU;
 pub use crate::date::Date;
+pub use crate::history::{parse_duration, History, Summary};
+pub use crate::icons::{Icon, IconPack};
+pub use crate::latency::Latency;
 pub use crate::memory::Memory;
+pub use crate::metrics::{Metric, MetricKind, MetricsEndpoint};
 pub use crate::mpd::MPD;
 pub use crate::network::Network;
+pub use crate::sink::{Pipe, Sink, Stdout, XSetRoot};
+pub use crate::sparkline::{Scale, Sparkline};
+pub use crate::template::{Template, TemplateError, Value};
+pub use crate::thresholds::{Direction, Thresholds};
+pub use crate::units::{Prefix, Unit, Units};
+pub use crate::usage::Usage;
+pub use crate::vpn::Vpn;
 pub use crate::wifi::Wifi;
+pub use chrono::Locale;
 
 #[async_trait]
 pub trait Widget {
     async fn get_output(&self) -> WidgetOutput;
     fn interval(&self) -> Duration;
+
+    /// Name used to address the widget over the control socket.
+    fn name(&self) -> &str {
+        ""
+    }
+
+    /// Handle a command sent over the control socket, returning whether it
+    /// was understood. The widget is redrawn right after.
+    fn on_command(&self, _command: &str) -> bool {
+        false
+    }
 }
 
 #[derive(Debug)]
@@ -37,12 +79,34 @@ pub struct WidgetOutput {
     text: String,
     use_default_background: bool,
     use_default_foreground: bool,
+    state: WidgetState,
+    metrics: Vec<Metric>,
+}
+
+/// What a widget is currently showing, used by `HideWhen` rules.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WidgetState {
+    Normal,
+    /// The value couldn't be read, e.g. "No MPD" or "No Battery"
+    Error,
+    /// A widget specific state such as `"disconnected"` or `"paused"`
+    State(&'static str),
 }
 
 type Handler = Box<dyn Widget + Send + Sync + 'static>;
 
+enum Event {
+    Output(usize, WidgetOutput),
+    /// Time to redraw the bars with scrolling text
+    Tick,
+}
+
 pub struct Barr {
     widgets: Vec<Arc<Handler>>,
+    bars: Vec<Bar>,
+    metrics_endpoint: Option<MetricsEndpoint>,
+    history: Option<History>,
+    control_socket: Option<PathBuf>,
 }
 
 impl Default for Barr {
@@ -53,68 +117,126 @@ impl Default for Barr {
 
 impl Barr {
     pub fn new() -> Self {
-        Self { widgets: vec![] }
+        Self {
+            widgets: vec![],
+            bars: vec![],
+            metrics_endpoint: None,
+            history: None,
+            control_socket: None,
+        }
     }
 
-    pub fn add_widget(&mut self, widget: Handler) {
+    /// Register a widget, it is sampled once no matter how many bars show it.
+    pub fn add_widget(&mut self, widget: Handler) -> WidgetId {
         self.widgets.push(Arc::new(widget));
+        WidgetId(self.widgets.len() - 1)
+    }
+
+    /// Without any bar, every widget is shown on the root window in the order
+    /// they were added.
+    pub fn add_bar(&mut self, bar: Bar) {
+        self.bars.push(bar);
+    }
+
+    /// Serve the latest samples of every widget in the OpenMetrics text
+    /// format on `GET /metrics`.
+    pub fn serve_metrics(&mut self, endpoint: MetricsEndpoint) {
+        self.metrics_endpoint = Some(endpoint);
+    }
+
+    /// Append the samples of every widget to a rolling CSV log, see `History`.
+    pub fn record_history(&mut self, history: History) {
+        self.history = Some(history);
+    }
+
+    /// Accept `<widget> <command>` lines on a Unix socket, see
+    /// `send_command`.
+    pub fn listen(&mut self, path: PathBuf) {
+        self.control_socket = Some(path);
     }
 
     pub async fn run(&mut self) {
-        let black = "#0F1419";
-        let white = "white";
-        let sep = "\u{e0b0}";
+        if self.bars.is_empty() {
+            let mut bar = Bar::new(XSetRoot::new());
+            for i in 0..self.widgets.len() {
+                bar.add_widget(WidgetId(i));
+            }
+            self.bars.push(bar);
+        }
 
-        let mut outs: Vec<String> = vec!["".to_owned(); self.widgets.len()];
+        let mut outputs: Vec<Option<WidgetOutput>> = self.widgets.iter().map(|_| None).collect();
+
+        let registry: metrics::Registry = Arc::new(Mutex::new(vec![vec![]; self.widgets.len()]));
+        if let Some(endpoint) = self.metrics_endpoint.clone() {
+            Task::spawn(metrics::serve(endpoint, registry.clone())).detach();
+        }
+
+        let recorder = self.history.take().map(History::spawn);
 
         let (sender, receiver) = channel::bounded(100);
+        let mut wakers = vec![];
         for (i, widget) in self.widgets.iter().enumerate() {
             let widget = widget.clone();
             let sender = sender.clone();
+            // Lets a command redraw the widget before its interval is up
+            let (waker, wake) = channel::bounded(1);
+            wakers.push(waker);
 
             Task::spawn(async move {
                 loop {
-                    let mut out = widget.get_output().await;
-
-                    let (fg, bg) = if i % 2 == 0 {
-                        (black, white)
-                    } else {
-                        (white, black)
-                    };
-
-                    if out.use_default_foreground && out.use_default_background {
-                        out.text = format!(
-                            "<span background='{}' foreground='{}'> {} </span>",
-                            bg, fg, out.text
-                        );
-                    } else if out.use_default_foreground {
-                        out.text = format!("<span foreground='{}'> {} </span>", fg, out.text);
-                    } else if out.use_default_background {
-                        out.text = format!("<span foreground='{}'> {} </span>", bg, out.text);
-                    }
-                    if !(i == 0 && fg == white) {
-                        out.text = format!(
-                            "<span background='{}' foreground='{}'>{}</span>{}",
-                            bg, fg, sep, out.text
-                        );
+                    let out = widget.get_output().await;
+                    sender.send(Event::Output(i, out)).await.unwrap();
+
+                    let woken = future::timeout(widget.interval(), wake.recv()).await;
+                    if let Ok(Err(_)) = woken {
+                        // Closed, nothing is left to wake the widget
+                        Timer::after(widget.interval()).await;
                     }
-                    sender.send((i, out)).await.unwrap();
+                }
+            })
+            .detach();
+        }
+
+        if let Some(path) = self.control_socket.clone() {
+            Task::spawn(control::serve(path, self.widgets.clone(), wakers)).detach();
+        }
+
+        let marquee_interval = self.bars.iter().filter_map(Bar::marquee_interval).min();
+        if let Some(interval) = marquee_interval {
+            let interval = interval.max(Duration::from_millis(50));
+            let sender = sender.clone();
 
-                    Timer::after(widget.interval()).await;
+            Task::spawn(async move {
+                loop {
+                    Timer::after(interval).await;
+                    sender.send(Event::Tick).await.unwrap();
                 }
             })
             .detach();
         }
 
         loop {
-            let (i, output) = receiver.recv().await.unwrap();
-            outs[i] = output.text;
-
-            Command::new("xsetroot")
-                .arg("-name")
-                .arg(outs.join(""))
-                .output()
-                .expect("failed to execute process");
+            let (i, output) = match receiver.recv().await.unwrap() {
+                Event::Output(i, output) => (i, output),
+                Event::Tick => {
+                    for bar in self.bars.iter_mut() {
+                        if bar.marquee_interval().is_some() {
+                            bar.show(&outputs);
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(recorder) = &recorder {
+                recorder.record(&output.metrics);
+            }
+            registry.lock().unwrap()[i] = output.metrics.clone();
+            outputs[i] = Some(output);
+
+            for bar in self.bars.iter_mut().filter(|bar| bar.contains(WidgetId(i))) {
+                bar.show(&outputs);
+            }
         }
     }
 }
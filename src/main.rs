@@ -1,32 +1,153 @@
-use barr::{Alsa, Barr, Battery, Brightness, Date, Memory, Network, Wifi, CPU, MPD};
+use barr::{
+    default_socket_path, parse_duration, send_command, Alsa, Barr, Battery, Brightness, Date,
+    History, Memory, MetricsEndpoint, Network, Wifi, CPU, MPD,
+};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::time::Duration;
 
 macro_rules! widgets {
-    ($($widget:expr),*$(,)?) => {{
-        let mut barr = Barr::new();
+    ($barr:ident => $config:expr; $($widget:expr),*$(,)?) => {{
+        let mut $barr = Barr::new();
+        $config;
 
         smol::run(async {
             $(
-                barr.add_widget(Box::new($widget));
+                $barr.add_widget(Box::new($widget));
             )*
 
-            barr.run().await;
+            $barr.run().await;
         });
     }};
 }
 
+const HISTORY_RETENTION: Duration = Duration::from_secs(60 * 60 * 24 * 30);
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("history") => return history(&args[1..]),
+        Some("msg") => return msg(&args[1..]),
+        _ => {}
+    }
+
+    let record_history = args.iter().any(|arg| arg == "--history");
+    let listen = args.iter().any(|arg| arg == "--listen");
+    let metrics = match args.iter().position(|arg| arg == "--metrics") {
+        Some(i) => match args.get(i + 1) {
+            Some(endpoint) => Some(metrics_endpoint(endpoint)),
+            None => {
+                eprintln!("usage: barr --metrics <address:port|socket path>");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
     let sec = Duration::from_secs(1);
 
     widgets!(
+        barr => {
+            if record_history {
+                barr.record_history(History::new(History::default_path(), HISTORY_RETENTION));
+            }
+            if listen {
+                barr.listen(default_socket_path());
+            }
+            if let Some(endpoint) = metrics {
+                barr.serve_metrics(endpoint);
+            }
+        };
         Alsa::new(sec),
         MPD::new(sec).await,
         Brightness::new(sec),
         Memory::new(sec),
         CPU::new(sec),
         Wifi::new(sec),
-        Network::new(sec, "wlp2s0"),
+        Network::auto(sec),
         Battery::new(sec),
         Date::new(sec)
+            .alternate_format("%H:%M")
+            .and_then(|date| date.alternate_format("%A, %d %B %Y"))
+            .and_then(|date| date.alternate_format("week %V"))
+            .and_then(|date| date.alternate_format("day %j"))
+            .unwrap()
     );
 }
+
+/// `--metrics 127.0.0.1:9100` serves over TCP, anything else is taken as the
+/// path of a Unix socket
+fn metrics_endpoint(endpoint: &str) -> MetricsEndpoint {
+    match endpoint.parse::<SocketAddr>() {
+        Ok(addr) => MetricsEndpoint::Tcp(addr),
+        Err(_) => MetricsEndpoint::Unix(PathBuf::from(endpoint)),
+    }
+}
+
+/// `barr msg <widget> <command>`, e.g. `barr msg date next`, to a barr
+/// started with `--listen`
+fn msg(args: &[String]) {
+    let (widget, command) = match args {
+        [widget, command] => (widget, command),
+        _ => {
+            eprintln!("usage: barr msg <widget> <command>");
+            std::process::exit(1);
+        }
+    };
+
+    let path = default_socket_path();
+    match send_command(&path, widget, command) {
+        Ok(answer) if answer == "ok" => {}
+        Ok(answer) => {
+            eprintln!("{}", answer);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Failed to reach barr on {:?}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `barr history <widget> [--since <duration>]`
+fn history(args: &[String]) {
+    let usage = "usage: barr history <widget> [--since <duration>]";
+
+    let widget = match args.first() {
+        Some(widget) => widget,
+        None => {
+            eprintln!("{}", usage);
+            std::process::exit(1);
+        }
+    };
+
+    let since = match args.get(1).map(String::as_str) {
+        None => Duration::from_secs(60 * 60),
+        Some("--since") => match args.get(2).and_then(|s| parse_duration(s)) {
+            Some(since) => since,
+            None => {
+                eprintln!("invalid duration, expected e.g. `30m`, `1h` or `7d`");
+                std::process::exit(1);
+            }
+        },
+        Some(_) => {
+            eprintln!("{}", usage);
+            std::process::exit(1);
+        }
+    };
+
+    let history = History::new(History::default_path(), HISTORY_RETENTION);
+    match history.summarize(widget, since) {
+        Ok(summaries) if summaries.is_empty() => {
+            println!("No samples for `{}` in {:?}", widget, history.path());
+        }
+        Ok(summaries) => {
+            for summary in summaries {
+                println!("{}", summary);
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to read {:?}: {}", history.path(), e);
+            std::process::exit(1);
+        }
+    }
+}
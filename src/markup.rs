@@ -0,0 +1,140 @@
+//! Helpers to measure and cut Pango markup without breaking its tags.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum Token<'a> {
+    /// An opening or closing tag, e.g. `<span foreground='red'>` or `</b>`
+    Tag(&'a str),
+    /// An entity such as `&amp;`, displayed as a single character
+    Entity(&'a str),
+    Text(&'a str),
+}
+
+pub(crate) fn tokenize(markup: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = markup;
+
+    while !rest.is_empty() {
+        let (token, len) = match rest.as_bytes()[0] {
+            b'<' => match rest.find('>') {
+                Some(end) => (Token::Tag(&rest[..=end]), end + 1),
+                None => (Token::Text(rest), rest.len()),
+            },
+            b'&' => match rest.find(';') {
+                Some(end) if !rest[1..end].contains(|c: char| c.is_whitespace() || c == '&') => {
+                    (Token::Entity(&rest[..=end]), end + 1)
+                }
+                _ => (Token::Text(&rest[..1]), 1),
+            },
+            _ => {
+                let end = rest.find(['<', '&']).unwrap_or(rest.len());
+                (Token::Text(&rest[..end]), end)
+            }
+        };
+        tokens.push(token);
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// Width of the displayed text in terminal-like columns, tags excluded.
+pub(crate) fn width(markup: &str) -> usize {
+    tokenize(markup)
+        .iter()
+        .map(|token| match token {
+            Token::Tag(_) => 0,
+            Token::Entity(_) => 1,
+            Token::Text(text) => text.width(),
+        })
+        .sum()
+}
+
+/// Keep the display columns `start..start + width`, cutting only between
+/// grapheme clusters. Every tag is kept so the nesting stays valid, tags
+/// around dropped text just end up empty.
+pub(crate) fn slice(markup: &str, start: usize, width: usize) -> String {
+    let end = start + width;
+    let mut column = 0;
+    let mut out = String::new();
+
+    let mut push = |out: &mut String, s: &str, w: usize| {
+        if column >= start && column + w <= end {
+            out.push_str(s);
+        }
+        column += w;
+    };
+
+    for token in tokenize(markup) {
+        match token {
+            Token::Tag(tag) => out.push_str(tag),
+            Token::Entity(entity) => push(&mut out, entity, 1),
+            Token::Text(text) => {
+                for grapheme in text.graphemes(true) {
+                    push(&mut out, grapheme, grapheme.width());
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Cut `markup` to at most `max_width` columns, ending with an ellipsis when
+/// anything was cut.
+pub(crate) fn truncate(markup: &str, max_width: usize) -> String {
+    if width(markup) <= max_width {
+        return markup.to_string();
+    }
+    match max_width {
+        0 => slice(markup, 0, 0),
+        _ => slice(markup, 0, max_width - 1) + "…",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_text_only() {
+        assert_eq!(width("<span foreground='red'>abc</span>"), 3);
+        assert_eq!(width("a&amp;b"), 3);
+        // A lone `&` isn't an entity
+        assert_eq!(width("a & b"), 5);
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn keeps_combining_marks_with_their_base() {
+        assert_eq!(slice("e\u{301}tude", 0, 1), "e\u{301}");
+        assert_eq!(truncate("e\u{301}e\u{301}e\u{301}", 2), "e\u{301}…");
+    }
+
+    #[test]
+    fn never_splits_wide_characters() {
+        // The second character would straddle the cut
+        assert_eq!(slice("日本語", 0, 3), "日");
+        assert_eq!(slice("日本語", 1, 4), "本");
+        assert_eq!(truncate("日本語", 4), "日…");
+        assert_eq!(truncate("日本語", 6), "日本語");
+    }
+
+    #[test]
+    fn entities_are_one_column() {
+        assert_eq!(slice("a&amp;b", 1, 1), "&amp;");
+        assert_eq!(slice("a&amp;b", 0, 1), "a");
+        assert_eq!(truncate("&lt;&lt;&lt;", 2), "&lt;…");
+    }
+
+    #[test]
+    fn keeps_tags_balanced() {
+        assert_eq!(
+            truncate("<span foreground='red'>long text</span> tail", 5),
+            "<span foreground='red'>long</span>…"
+        );
+        assert_eq!(slice("<b>ab</b><i>cd</i>", 2, 2), "<b></b><i>cd</i>");
+        assert_eq!(truncate("<b>abc</b>", 0), "<b></b>");
+    }
+}
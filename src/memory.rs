@@ -1,16 +1,28 @@
-use crate::{Widget, WidgetOutput};
+use crate::{
+    Icon, IconPack, Metric, Scale, Sparkline, Template, TemplateError, Thresholds, Units, Widget,
+    WidgetOutput, WidgetState,
+};
 use async_trait::async_trait;
 use std::sync::Mutex;
 use std::time::Duration;
 use sysinfo::{System, SystemExt};
 
+/// Used memory.
+///
+/// Format placeholders: `{icon}`, `{percent}`, `{used}`, `{total}`,
+/// `{sparkline}` (recent usage). Absolute sizes are formatted with `units`.
 pub struct Memory {
     interval: Duration,
     system: Mutex<System>,
-    tresholds: Vec<(f64, String)>,
-    icon: String,
+    thresholds: Thresholds,
+    icons: IconPack,
+    format: Template,
+    units: Units,
+    sparkline: Sparkline,
 }
 
+const PLACEHOLDERS: &[&str] = &["icon", "percent", "used", "total", "sparkline"];
+
 #[async_trait]
 impl Widget for Memory {
     fn interval(&self) -> Duration {
@@ -18,23 +30,21 @@ impl Widget for Memory {
     }
 
     async fn get_output(&self) -> WidgetOutput {
-        let ram = self.get_used_ram_percentage();
-        let mut text = format!("{} {:.0}", self.icon, ram);
+        let (used, total) = self.get_used_and_total_ram();
+        let ram = (used / total) * 100_f64;
+        self.sparkline.push(ram);
+
+        let mut text = self.format.render(|name| match name {
+            "sparkline" => self.sparkline.render().into(),
+            "icon" => self.icons.icon(Icon::Memory, ram).into(),
+            "used" => self.units.format(used).into(),
+            "total" => self.units.format(total).into(),
+            _ => ram.into(),
+        });
 
         let mut use_default_fg = true;
 
-        let fg = {
-            let mut fg = None;
-            for (treshold, color) in self.tresholds.iter().rev() {
-                if ram >= *treshold {
-                    fg = Some(color);
-                    break;
-                }
-            }
-            fg
-        };
-
-        if let Some(fg) = fg {
+        if let Some(fg) = self.thresholds.color(ram) {
             text = format!("<span foreground='{}'>{}</span>", fg, text);
             use_default_fg = false;
         }
@@ -43,6 +53,12 @@ impl Widget for Memory {
             text,
             use_default_foreground: use_default_fg,
             use_default_background: true,
+            state: WidgetState::Normal,
+            metrics: vec![Metric::gauge(
+                "memory_used_percent",
+                "Used memory in percent of the total memory",
+                ram,
+            )],
         }
     }
 }
@@ -52,19 +68,58 @@ impl Memory {
         Self {
             interval,
             system: Mutex::new(System::new_all()),
-            tresholds: vec![
-                (35_f64, "#E9A072".to_string()),
-                (50_f64, "#F2665F".to_string()),
-                (80_f64, "#FF0000".to_string()),
-            ],
-            icon: "\u{f2db}".to_string(),
+            thresholds: Thresholds::ascending(&[
+                (35_f64, "#E9A072"),
+                (50_f64, "#F2665F"),
+                (80_f64, "#FF0000"),
+            ]),
+            icons: IconPack::default(),
+            format: Template::parse("{icon} {percent}", PLACEHOLDERS).unwrap(),
+            units: Units::bytes(),
+            sparkline: Sparkline::new(10, Scale::Fixed(0.0, 100.0)),
         }
     }
 
-    #[allow(clippy::cast_precision_loss)]
+    pub fn thresholds(mut self, thresholds: Thresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Keep the last `length` samples for `{sparkline}`.
+    pub fn sparkline(mut self, length: usize, scale: Scale) -> Self {
+        self.sparkline = Sparkline::new(length, scale);
+        self
+    }
+
+    pub fn icons(mut self, icons: IconPack) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
+    }
+
+    pub fn units(mut self, units: Units) -> Self {
+        self.units = units;
+        self
+    }
+
     pub fn get_used_ram_percentage(&self) -> f64 {
+        let (used, total) = self.get_used_and_total_ram();
+        (used / total) * 100_f64
+    }
+
+    /// Used and total memory in bytes
+    #[allow(clippy::cast_precision_loss)]
+    fn get_used_and_total_ram(&self) -> (f64, f64) {
         let mut s = self.system.lock().unwrap();
         s.refresh_memory();
-        (s.get_used_memory() as f64 / s.get_total_memory() as f64) * 100_f64
+        // `sysinfo` reports KiB
+        (
+            s.get_used_memory() as f64 * 1024_f64,
+            s.get_total_memory() as f64 * 1024_f64,
+        )
     }
 }
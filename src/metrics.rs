@@ -0,0 +1,227 @@
+use async_std::io::{Read, Write};
+use async_std::net::TcpListener;
+use async_std::os::unix::net::UnixListener;
+use async_std::prelude::*;
+use smol::Task;
+
+use crate::control::remove_stale_socket;
+
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+pub(crate) type Registry = Arc<Mutex<Vec<Vec<Metric>>>>;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MetricKind {
+    Gauge,
+    Counter,
+}
+
+/// A single numeric sample reported by a widget alongside its text.
+///
+/// Names are given without the `barr_` prefix (and, for counters, without the
+/// `_total` suffix); both are added when the sample is exported.
+#[derive(Debug, Clone)]
+pub struct Metric {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: MetricKind,
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+impl Metric {
+    pub fn gauge(name: &'static str, help: &'static str, value: f64) -> Self {
+        Self {
+            name,
+            help,
+            kind: MetricKind::Gauge,
+            labels: vec![],
+            value,
+        }
+    }
+
+    pub fn counter(name: &'static str, help: &'static str, value: f64) -> Self {
+        Self {
+            kind: MetricKind::Counter,
+            ..Self::gauge(name, help, value)
+        }
+    }
+
+    pub fn label(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.labels.push((key, value.into()));
+        self
+    }
+}
+
+/// Where the OpenMetrics text is served, see `Barr::serve_metrics`.
+#[derive(Debug, Clone)]
+pub enum MetricsEndpoint {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+/// Encode `metrics` in the OpenMetrics text format, grouping samples of the
+/// same family under one `# HELP`/`# TYPE` header.
+pub(crate) fn encode(metrics: &[Metric]) -> String {
+    let mut families: Vec<(&Metric, Vec<&Metric>)> = vec![];
+    for metric in metrics {
+        match families.iter_mut().find(|(m, _)| m.name == metric.name) {
+            Some((_, samples)) => samples.push(metric),
+            None => families.push((metric, vec![metric])),
+        }
+    }
+
+    let mut out = String::new();
+    for (family, samples) in families {
+        let (kind, suffix) = match family.kind {
+            MetricKind::Gauge => ("gauge", ""),
+            MetricKind::Counter => ("counter", "_total"),
+        };
+        let _ = writeln!(out, "# HELP barr_{} {}", family.name, family.help);
+        let _ = writeln!(out, "# TYPE barr_{} {}", family.name, kind);
+
+        for sample in samples {
+            let _ = write!(out, "barr_{}{}", sample.name, suffix);
+            if !sample.labels.is_empty() {
+                let labels: Vec<String> = sample
+                    .labels
+                    .iter()
+                    .map(|(k, v)| format!("{}=\"{}\"", k, escape(v)))
+                    .collect();
+                let _ = write!(out, "{{{}}}", labels.join(","));
+            }
+            let _ = writeln!(out, " {}", sample.value);
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub(crate) async fn serve(endpoint: MetricsEndpoint, registry: Registry) {
+    match endpoint {
+        MetricsEndpoint::Tcp(addr) => {
+            let listener = match TcpListener::bind(addr).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("Failed to bind metrics endpoint {}: {}", addr, e);
+                    return;
+                }
+            };
+            let mut incoming = listener.incoming();
+            while let Some(stream) = incoming.next().await {
+                if let Ok(stream) = stream {
+                    let registry = registry.clone();
+                    Task::spawn(async move { respond(stream, &registry).await }).detach();
+                }
+            }
+        }
+        MetricsEndpoint::Unix(path) => {
+            if let Err(e) = remove_stale_socket(&path).await {
+                eprintln!("Failed to bind metrics endpoint {:?}: {}", path, e);
+                return;
+            }
+            let listener = match UnixListener::bind(&path).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("Failed to bind metrics endpoint {:?}: {}", path, e);
+                    return;
+                }
+            };
+            let mut incoming = listener.incoming();
+            while let Some(stream) = incoming.next().await {
+                if let Ok(stream) = stream {
+                    let registry = registry.clone();
+                    Task::spawn(async move { respond(stream, &registry).await }).detach();
+                }
+            }
+        }
+    }
+}
+
+/// Answer a single HTTP request: `GET /metrics` gets the current samples,
+/// everything else a 404.
+async fn respond<S>(mut stream: S, registry: &Registry)
+where
+    S: Read + Write + Unpin,
+{
+    let mut request = vec![];
+    let mut buf = [0_u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+    let method = request_line.next();
+    let path = request_line.next();
+
+    let response = if method == Some("GET") && path == Some("/metrics") {
+        let metrics: Vec<Metric> = registry.lock().unwrap().iter().flatten().cloned().collect();
+        let body = encode(&metrics);
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_families() {
+        let metrics = [
+            Metric::gauge("cpu_usage_percent", "CPU usage", 12.5),
+            Metric::counter("network_rx_bytes", "Received bytes", 1024.0)
+                .label("interface", "eth0"),
+            Metric::counter("network_rx_bytes", "Received bytes", 2048.0)
+                .label("interface", "wlan0"),
+        ];
+        assert_eq!(
+            encode(&metrics),
+            "# HELP barr_cpu_usage_percent CPU usage
+# TYPE barr_cpu_usage_percent gauge
+barr_cpu_usage_percent 12.5
+# HELP barr_network_rx_bytes Received bytes
+# TYPE barr_network_rx_bytes counter
+barr_network_rx_bytes_total{interface=\"eth0\"} 1024
+barr_network_rx_bytes_total{interface=\"wlan0\"} 2048
+# EOF
+"
+        );
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let metrics = [Metric::gauge("wifi_signal_dbm", "Signal", -56.0)
+            .label("ssid", "a \"b\"\\c\nd")
+            .label("interface", "wlan0")];
+        assert!(encode(&metrics).contains(
+            "barr_wifi_signal_dbm{ssid=\"a \\\"b\\\"\\\\c\\nd\",interface=\"wlan0\"} -56\n"
+        ));
+    }
+
+    #[test]
+    fn empty_is_only_eof() {
+        assert_eq!(encode(&[]), "# EOF\n");
+    }
+}
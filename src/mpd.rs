@@ -1,4 +1,4 @@
-use crate::{Widget, WidgetOutput};
+use crate::{Template, TemplateError, Widget, WidgetOutput, WidgetState};
 
 use async_std::net::TcpStream;
 use async_std::prelude::*;
@@ -78,6 +78,8 @@ impl Display for StatusFlags {
 struct Status {
     flags: StatusFlags,
     percentage: u8,
+    elapsed: f64,
+    duration: f64,
     state: State,
 }
 
@@ -87,13 +89,22 @@ struct Song {
     title: String,
 }
 
+/// Current song of the MPD server on `localhost:6600`.
+///
+/// Format placeholders (`format` while playing or paused, `stop_format` while
+/// stopped): `{artist}`, `{title}`, `{percent}`, `{elapsed}`, `{duration}`,
+/// `{flags}`.
 #[derive(Debug)]
 pub struct MPD {
     stream: Mutex<Result<TcpStream, MPDError>>,
     interval: Duration,
     pause_color: String,
+    format: Template,
+    stop_format: Template,
 }
 
+const PLACEHOLDERS: &[&str] = &["artist", "title", "percent", "elapsed", "duration", "flags"];
+
 #[derive(Debug, Copy, Clone)]
 pub enum MPDError {
     ConnectionError,
@@ -134,6 +145,8 @@ impl Widget for MPD {
                     text: "<span foreground='grey'>Empty Playlist</span>".to_string(),
                     use_default_foreground: false,
                     use_default_background: true,
+                    state: WidgetState::State("empty"),
+                    metrics: vec![],
                 };
             }
             Err(_) => {
@@ -144,6 +157,8 @@ impl Widget for MPD {
                         text: "<span foreground='red'>No MPD</span>".to_string(),
                         use_default_foreground: false,
                         use_default_background: true,
+                        state: WidgetState::Error,
+                        metrics: vec![],
                     };
                 }
             }
@@ -165,6 +180,8 @@ impl Widget for MPD {
                     text: "<span foreground='red'>No MPD</span>".to_string(),
                     use_default_foreground: false,
                     use_default_background: true,
+                    state: WidgetState::Error,
+                    metrics: vec![],
                 };
             }
         }
@@ -172,12 +189,14 @@ impl Widget for MPD {
         if song.is_ok() && status.is_ok() {
             let song = song.unwrap();
             let status = status.unwrap();
-            let mut output = format!(
-                "[{}] {} - {} [{}]",
-                status.percentage, song.artist, song.title, status.flags
-            );
+            let mut output = Self::render(&self.format, &song, &status);
 
             let (mut use_default_foreground, use_default_background) = (true, true);
+            let state = match status.state {
+                State::Pause => WidgetState::State("paused"),
+                State::Stop => WidgetState::State("stopped"),
+                State::Play => WidgetState::State("playing"),
+            };
             match status.state {
                 State::Pause => {
                     use_default_foreground = false;
@@ -188,8 +207,9 @@ impl Widget for MPD {
                 }
                 State::Stop => {
                     output = format!(
-                        "<span foreground='{}'><i>/{} - {} [{}]/</i></span>",
-                        self.pause_color, song.artist, song.title, status.flags
+                        "<span foreground='{}'><i>{}</i></span>",
+                        self.pause_color,
+                        Self::render(&self.stop_format, &song, &status)
                     );
                 }
                 State::Play => (),
@@ -199,12 +219,16 @@ impl Widget for MPD {
                 text: output,
                 use_default_foreground,
                 use_default_background,
+                state,
+                metrics: vec![],
             }
         } else {
             WidgetOutput {
                 text: "<span foreground='red'>No MPD</span>".to_string(),
                 use_default_foreground: false,
                 use_default_background: true,
+                state: WidgetState::Error,
+                metrics: vec![],
             }
         }
     }
@@ -227,9 +251,39 @@ impl MPD {
             interval,
             stream,
             pause_color,
+            format: Template::parse("[{percent}] {artist} - {title} [{flags}]", PLACEHOLDERS)
+                .unwrap(),
+            stop_format: Template::parse("/{artist} - {title} [{flags}]/", PLACEHOLDERS).unwrap(),
         }
     }
 
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
+    }
+
+    pub fn stop_format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.stop_format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
+    }
+
+    fn render(format: &Template, song: &Song, status: &Status) -> String {
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let minutes = |secs: f64| {
+            let secs = secs as u64;
+            format!("{}:{:02}", secs / 60, secs % 60)
+        };
+
+        format.render(|name| match name {
+            "artist" => song.artist.as_str().into(),
+            "title" => song.title.as_str().into(),
+            "percent" => f64::from(status.percentage).into(),
+            "elapsed" => minutes(status.elapsed).into(),
+            "duration" => minutes(status.duration).into(),
+            _ => status.flags.to_string().into(),
+        })
+    }
+
     pub async fn reconnect(&self) -> bool {
         if let Ok(mut stream) = TcpStream::connect("localhost:6600").await {
             let mut buf = vec![0_u8; 1024];
@@ -332,6 +386,8 @@ impl MPD {
             flags,
             state,
             percentage: (elapsed * 100_f64 / duration).floor() as u8,
+            elapsed,
+            duration,
         })
     }
 }
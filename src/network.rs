@@ -1,11 +1,17 @@
-use crate::{Widget, WidgetOutput};
+use crate::{ifaddrs, route};
+use crate::{
+    Icon, IconPack, Metric, Scale, Sparkline, Template, TemplateError, Units, Usage, Value, Widget,
+    WidgetOutput, WidgetState,
+};
 use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
 use std::io;
 use std::io::Read;
+use std::net::IpAddr;
 use std::sync::Mutex;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 struct NetworkStats {
     pub rx_bytes: u64,
     pub tx_bytes: u64,
@@ -15,85 +21,678 @@ struct NetworkStats {
     pub tx_errors: u64,
 }
 
-pub struct Network<'a> {
+/// Receive and transmit rate of one or more network interfaces.
+///
+/// Format placeholders: `{rx}`, `{tx}` (icon and rate, highlighted above
+/// 1 MiB/s), `{rx_rate}`, `{tx_rate}` (rate only), `{rx_kib}`, `{tx_kib}`
+/// (bare KiB/s), `{rx_packets}`, `{tx_packets}` (packets/s), `{rx_errors}`,
+/// `{tx_errors}` (errors/s, packets and errors with one decimal by default),
+/// `{rx_sparkline}`, `{tx_sparkline}` (recent total rates), `{rx_peak}`,
+/// `{tx_peak}` (highest total rate within the peak window, a minute by
+/// default), `{today}`, `{today_rx}`, `{today_tx}`,
+/// `{month}`, `{month_rx}`, `{month_tx}` (data used, see `usage`), `{quota}`
+/// (percent of the quota used), `{interface}`, `{ipv4}`, `{ipv6}` (first
+/// address, for IPv6 the first that isn't link-local), `{ipv4_all}`,
+/// `{ipv6_all}`, `{gateway}`, `{speed}`, `{duplex}` (of the first wired
+/// interface). Rates are formatted with `units`.
+///
+/// Created with `auto` it follows the interface of the default route and
+/// starts over from a rate of 0, with empty sparklines and peaks, whenever
+/// that changes. With several
+/// interfaces the rates are summed up, or with `breakdown` the format is
+/// repeated for every interface, by default prefixed with its name.
+///
+/// Counter resets and wraparounds are detected, and no rate is shown for the
+/// first sample after a suspend. An interface that is down, without carrier
+/// or dormant is reported in the `down`, `no-carrier` or `dormant` state.
+/// While errors are counted the widget is shown in `warning_color` and the
+/// `errors` state. Once a quota is used up it turns red and goes into the
+/// `over-quota` state. A wired link slower than `expected_speed` or at half
+/// duplex is shown in `warning_color` and the `slow-link` state.
+///
+/// The shown rates can be smoothed per direction with an exponential moving
+/// average, metrics and peaks always use the measured rates.
+pub struct Network {
     interval: Duration,
-    interface: &'a str,
+    interface: Interface,
+    include_virtual: bool,
+    breakdown: bool,
+    format: Template,
+    /// Whether `format` was set rather than following `breakdown`
+    custom_format: bool,
+    units: Units,
+    warning_color: String,
+    expected_speed: Option<u32>,
+    usage: Option<Usage>,
+    rx_smoothing: f64,
+    tx_smoothing: f64,
 
-    network_stats: Mutex<NetworkStats>,
-    last_called: Mutex<Instant>,
+    last_samples: Mutex<HashMap<String, Sample>>,
+    /// Interface of the default route at the last sample in `Auto` mode
+    followed: Mutex<Option<String>>,
+    rx_peak: Peak,
+    tx_peak: Peak,
+    rx_sparkline: Sparkline,
+    tx_sparkline: Sparkline,
 
-    rx_icon: String,
-    tx_icon: String,
+    icons: IconPack,
 }
 
+enum Interface {
+    Named(String),
+    /// Whichever interface holds the default route
+    Auto,
+    /// Names or globs such as `en*`
+    Matching(Vec<String>),
+}
+
+/// Counters read at `time`, the base of the next rate.
+struct Sample {
+    stats: NetworkStats,
+    time: Instant,
+    // `Instant` stands still during suspend, the wall clock doesn't
+    wall: SystemTime,
+    /// Smoothed rates shown for this sample
+    rx_rate: f64,
+    tx_rate: f64,
+}
+
+/// Highest value seen within `window`.
+struct Peak {
+    window: Duration,
+    values: Mutex<VecDeque<(Instant, f64)>>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Link {
+    Up,
+    /// Administratively down
+    Down,
+    NoCarrier,
+    /// Up but waiting for e.g. 802.1X authentication
+    Dormant,
+}
+
+struct Rates {
+    interface: String,
+    stats: NetworkStats,
+    /// With the byte rates smoothed
+    per_second: PerSecond,
+    measured: PerSecond,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PerSecond {
+    rx: f64,
+    tx: f64,
+    rx_packets: f64,
+    tx_packets: f64,
+    rx_errors: f64,
+    tx_errors: f64,
+}
+
+const PLACEHOLDERS: &[&str] = &[
+    "rx",
+    "tx",
+    "rx_rate",
+    "tx_rate",
+    "rx_kib",
+    "tx_kib",
+    "rx_packets",
+    "tx_packets",
+    "rx_errors",
+    "tx_errors",
+    "today",
+    "today_rx",
+    "today_tx",
+    "month",
+    "month_rx",
+    "month_tx",
+    "quota",
+    "rx_sparkline",
+    "tx_sparkline",
+    "rx_peak",
+    "tx_peak",
+    "interface",
+    "ipv4",
+    "ipv6",
+    "ipv4_all",
+    "ipv6_all",
+    "gateway",
+    "speed",
+    "duplex",
+];
+
+const DEFAULT_FORMAT: &str = "{rx}  {tx}";
+/// Every interface shown on its own needs its name
+const BREAKDOWN_FORMAT: &str = "{interface} {rx}  {tx}";
+
 #[async_trait]
-impl Widget for Network<'_> {
+impl Widget for Network {
     fn interval(&self) -> Duration {
         self.interval
     }
 
     #[allow(clippy::cast_precision_loss)]
     async fn get_output(&self) -> WidgetOutput {
-        let new_network_stat = Self::get_network_stats(self.interface).unwrap();
         let end = Instant::now();
+        let wall = SystemTime::now();
+        let (rates, inactive) = {
+            let mut last_samples = self.last_samples.lock().unwrap();
 
-        let diff = end - *self.last_called.lock().unwrap();
-
-        let rx = (new_network_stat.rx_bytes - self.network_stats.lock().unwrap().rx_bytes) as f64
-            / diff.as_secs_f64();
-        let tx = (new_network_stat.tx_bytes - self.network_stats.lock().unwrap().tx_bytes) as f64
-            / diff.as_secs_f64();
+            let mut samples = HashMap::new();
+            let mut rates = vec![];
+            let mut inactive = None;
+            let interfaces = self.interfaces();
+            if let (Interface::Auto, Some(interface)) = (&self.interface, interfaces.first()) {
+                self.follow(interface);
+            }
+            for interface in interfaces {
+                match link_state(&interface) {
+                    Some(Link::Up) => {}
+                    Some(link) => {
+                        inactive.get_or_insert((interface, link));
+                        continue;
+                    }
+                    None => continue,
+                }
+                let stats = match Self::get_network_stats(&interface) {
+                    Ok(stats) => stats,
+                    Err(_) => continue,
+                };
+                let mut sample = Sample {
+                    stats,
+                    time: end,
+                    wall,
+                    rx_rate: 0.0,
+                    tx_rate: 0.0,
+                };
+                // None for the first sample, the default route moving to this
+                // interface, a counter reset or a suspend
+                let last = last_samples.get(&interface);
+                let (per_second, measured) = self.smoothed_rates(last, &mut sample);
 
-        let (rx_kb, rx_mb) = (rx / 1024.0, rx / 1024.0 / 1024.0);
-        let (tx_kb, tx_mb) = (tx / 1024.0, tx / 1024.0 / 1024.0);
+                // Unlike rates the bytes still count after a suspend
+                if let (Some(usage), Some(last)) = (&self.usage, last) {
+                    let rx = counter_delta(last.stats.rx_bytes, stats.rx_bytes);
+                    let tx = counter_delta(last.stats.tx_bytes, stats.tx_bytes);
+                    usage.add(&interface, rx.unwrap_or(0), tx.unwrap_or(0));
+                }
 
-        let rx = if rx_mb > 1.0 {
-            format!(
-                "<span foreground='blue'>{} <b>{:.2}</b></span>",
-                self.rx_icon, rx_mb
-            )
-        } else {
-            format!("{} {:.0}", self.rx_icon, rx_kb)
+                samples.insert(interface.clone(), sample);
+                rates.push(Rates {
+                    interface,
+                    stats,
+                    per_second,
+                    measured,
+                });
+            }
+            // Interfaces gone in between start over once they are back
+            *last_samples = samples;
+            (rates, inactive)
         };
+        // Once per sample rather than per interface
+        if let Some(usage) = &self.usage {
+            usage.flush().await;
+        }
 
-        let tx = if tx_mb > 1.0 {
-            format!(
-                "<span foreground='blue'>{} <b>{:.2}</b></span>",
-                self.tx_icon, tx_mb
-            )
+        if let (true, Some((interface, link))) = (rates.is_empty(), inactive) {
+            let state = match link {
+                Link::Down => "down",
+                Link::NoCarrier => "no-carrier",
+                _ => "dormant",
+            };
+            return WidgetOutput {
+                text: format!(
+                    "<span foreground='grey'>{}: {}</span>",
+                    interface,
+                    state.replace('-', " ")
+                ),
+                use_default_foreground: true,
+                use_default_background: true,
+                state: WidgetState::State(state),
+                metrics: vec![],
+            };
+        }
+        if rates.is_empty() {
+            return WidgetOutput {
+                text: "<span foreground='grey'>No Network</span>".to_string(),
+                use_default_foreground: true,
+                use_default_background: true,
+                state: WidgetState::State("disconnected"),
+                metrics: vec![],
+            };
+        }
+
+        let total = rates
+            .iter()
+            .fold(PerSecond::default(), |total, r| total.add(&r.per_second));
+        self.rx_sparkline.push(total.rx);
+        self.tx_sparkline.push(total.tx);
+
+        let measured = rates
+            .iter()
+            .fold(PerSecond::default(), |total, r| total.add(&r.measured));
+        self.rx_peak.push(end, measured.rx);
+        self.tx_peak.push(end, measured.tx);
+
+        let metrics = rates
+            .iter()
+            .flat_map(|r| self.metrics(&r.interface, &r.stats, r.measured.rx, r.measured.tx))
+            .collect();
+
+        let names: Vec<&str> = rates.iter().map(|r| r.interface.as_str()).collect();
+        let mut text = if self.breakdown {
+            rates
+                .iter()
+                .map(|r| self.render(&[r.interface.as_str()], &r.per_second))
+                .collect::<Vec<_>>()
+                .join(" | ")
         } else {
-            format!("{} {:.0}", self.tx_icon, tx_kb)
+            self.render(&names, &total)
         };
 
-        let text = format!("{}  {}", rx, tx);
-
-        let mut l = self.network_stats.lock().unwrap();
-        *l = new_network_stat;
+        let over_quota = match &self.usage {
+            Some(usage) => matches!(usage.check_quota(&names), Some(percent) if percent >= 100.0),
+            None => false,
+        };
+        let errors = total.rx_errors + total.tx_errors > 0.0;
+        let slow_link = match self.expected_speed {
+            Some(expected) => names
+                .iter()
+                .filter_map(|name| link_speed(name))
+                .any(|(speed, duplex)| speed < expected || duplex == "half"),
+            None => false,
+        };
 
-        let mut l = self.last_called.lock().unwrap();
-        *l = end;
+        let state = if over_quota {
+            text = format!("<span foreground='red'>{}</span>", text);
+            WidgetState::State("over-quota")
+        } else if errors {
+            text = format!("<span foreground='{}'>{}</span>", self.warning_color, text);
+            WidgetState::State("errors")
+        } else if slow_link {
+            text = format!("<span foreground='{}'>{}</span>", self.warning_color, text);
+            WidgetState::State("slow-link")
+        } else {
+            WidgetState::Normal
+        };
 
         WidgetOutput {
             text,
-            use_default_foreground: true,
+            use_default_foreground: state == WidgetState::Normal,
             use_default_background: true,
+            state,
+            metrics,
         }
     }
 }
 
-impl<'a> Network<'a> {
-    pub fn new(interval: Duration, interface: &'a str) -> Self {
-        let rx_icon = "\u{f0ed}".to_string();
-        let tx_icon = "\u{f0ee}".to_string();
+impl Network {
+    pub fn new(interval: Duration, interface: &str) -> Self {
+        Self::with_interface(interval, Interface::Named(interface.to_string()))
+    }
+
+    /// Follow the interface of the default route, read from `/proc/net/route`.
+    pub fn auto(interval: Duration) -> Self {
+        Self::with_interface(interval, Interface::Auto)
+    }
 
+    /// Every interface matching one of `patterns`, names or globs with `*`
+    /// and `?` such as `en*`. Virtual interfaces like `lo`, `docker0` or
+    /// `veth*` are only matched by a glob with `include_virtual`.
+    pub fn matching(interval: Duration, patterns: &[&str]) -> Self {
+        Self::with_interface(
+            interval,
+            Interface::Matching(patterns.iter().map(|p| (*p).to_string()).collect()),
+        )
+    }
+
+    fn with_interface(interval: Duration, interface: Interface) -> Self {
         Self {
             interval,
             interface,
-            network_stats: Mutex::new(Self::get_network_stats(interface).unwrap()),
-            last_called: Mutex::new(Instant::now()),
-            rx_icon,
-            tx_icon,
+            include_virtual: false,
+            breakdown: false,
+            format: Template::parse(DEFAULT_FORMAT, PLACEHOLDERS).unwrap(),
+            custom_format: false,
+            units: Units::bytes().per_second(true),
+            warning_color: "#E9A072".to_string(),
+            expected_speed: None,
+            usage: None,
+            rx_smoothing: 1.0,
+            tx_smoothing: 1.0,
+            last_samples: Mutex::new(HashMap::new()),
+            followed: Mutex::new(None),
+            rx_peak: Peak::new(Duration::from_secs(60)),
+            tx_peak: Peak::new(Duration::from_secs(60)),
+            rx_sparkline: Sparkline::new(10, Scale::Auto),
+            tx_sparkline: Sparkline::new(10, Scale::Auto),
+            icons: IconPack::default(),
+        }
+    }
+
+    pub fn include_virtual(mut self, include_virtual: bool) -> Self {
+        self.include_virtual = include_virtual;
+        self
+    }
+
+    /// Repeat the format for every interface instead of summing up the rates.
+    pub fn breakdown(mut self, breakdown: bool) -> Self {
+        self.breakdown = breakdown;
+        if !self.custom_format {
+            let format = if breakdown {
+                BREAKDOWN_FORMAT
+            } else {
+                DEFAULT_FORMAT
+            };
+            self.format = Template::parse(format, PLACEHOLDERS).unwrap();
         }
+        self
+    }
+
+    pub fn icons(mut self, icons: IconPack) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.format = Template::parse(format, PLACEHOLDERS)?;
+        self.custom_format = true;
+        Ok(self)
+    }
+
+    /// Keep the last `length` rates for `{rx_sparkline}` and `{tx_sparkline}`,
+    /// a `Fixed` scale is in bytes per second.
+    pub fn sparkline(mut self, length: usize, scale: Scale) -> Self {
+        self.rx_sparkline = Sparkline::new(length, scale);
+        self.tx_sparkline = Sparkline::new(length, scale);
+        self
+    }
+
+    pub fn units(mut self, units: Units) -> Self {
+        self.units = units;
+        self
+    }
+
+    /// Color of the whole widget while errors are counted.
+    pub fn warning_color(mut self, color: &str) -> Self {
+        self.warning_color = color.to_string();
+        self
+    }
+
+    /// Weight of the newest receive rate between 0 and 1, the lower the
+    /// smoother. 1 shows the measured rate.
+    pub fn rx_smoothing(mut self, alpha: f64) -> Self {
+        self.rx_smoothing = alpha.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Weight of the newest transmit rate, see `rx_smoothing`.
+    pub fn tx_smoothing(mut self, alpha: f64) -> Self {
+        self.tx_smoothing = alpha.clamp(0.0, 1.0);
+        self
+    }
+
+    /// How far back `{rx_peak}` looks.
+    pub fn rx_peak_window(mut self, window: Duration) -> Self {
+        self.rx_peak = Peak::new(window);
+        self
+    }
+
+    /// How far back `{tx_peak}` looks.
+    pub fn tx_peak_window(mut self, window: Duration) -> Self {
+        self.tx_peak = Peak::new(window);
+        self
+    }
+
+    /// Warn when a wired link comes up slower than `mbps`, e.g. 1000, or at
+    /// half duplex, which usually means a bad cable.
+    pub fn expected_speed(mut self, mbps: u32) -> Self {
+        self.expected_speed = Some(mbps);
+        self
+    }
+
+    /// Keep track of the data used, see `Usage`.
+    pub fn usage(mut self, usage: Usage) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Start the history over once the default route moved to `interface`.
+    fn follow(&self, interface: &str) {
+        let mut followed = self.followed.lock().unwrap();
+        if followed.as_deref() == Some(interface) {
+            return;
+        }
+        *followed = Some(interface.to_string());
+
+        self.rx_sparkline.clear();
+        self.tx_sparkline.clear();
+        self.rx_peak.clear();
+        self.tx_peak.clear();
+    }
+
+    /// Rates per second since `last`, `None` when they can't be trusted.
+    #[allow(clippy::cast_precision_loss)]
+    fn rates(&self, last: &Sample, sample: &Sample) -> Option<PerSecond> {
+        let gap = sample.wall.duration_since(last.wall).ok()?;
+        if gap > (self.interval * 3).max(Duration::from_secs(5)) {
+            return None;
+        }
+
+        let secs = (sample.time - last.time).as_secs_f64();
+        let rate = |counter: fn(&NetworkStats) -> u64| {
+            counter_delta(counter(&last.stats), counter(&sample.stats))
+                .map(|delta| delta as f64 / secs)
+        };
+        Some(PerSecond {
+            rx: rate(|s| s.rx_bytes)?,
+            tx: rate(|s| s.tx_bytes)?,
+            rx_packets: rate(|s| s.rx_packets)?,
+            tx_packets: rate(|s| s.tx_packets)?,
+            rx_errors: rate(|s| s.rx_errors)?,
+            tx_errors: rate(|s| s.tx_errors)?,
+        })
+    }
+
+    /// Shown and measured rates since `last`, both 0 when there is no
+    /// trusted rate. The shown rates are kept in `sample` for the next one.
+    fn smoothed_rates(&self, last: Option<&Sample>, sample: &mut Sample) -> (PerSecond, PerSecond) {
+        let (last, measured) = match last.and_then(|last| Some((last, self.rates(last, sample)?))) {
+            Some(rated) => rated,
+            // Smoothing toward 0 would still show a rate after a suspend
+            None => return (PerSecond::default(), PerSecond::default()),
+        };
+
+        let mut per_second = measured;
+        per_second.rx = ema(self.rx_smoothing, last.rx_rate, measured.rx);
+        per_second.tx = ema(self.tx_smoothing, last.tx_rate, measured.tx);
+        sample.rx_rate = per_second.rx;
+        sample.tx_rate = per_second.tx;
+        (per_second, measured)
+    }
+
+    fn interfaces(&self) -> Vec<String> {
+        match &self.interface {
+            Interface::Named(name) => vec![name.clone()],
+            Interface::Auto => match route::default_route() {
+                Ok(Some(route)) => vec![route.interface],
+                _ => vec![],
+            },
+            Interface::Matching(patterns) => {
+                let mut names: Vec<String> = std::fs::read_dir("/sys/class/net")
+                    .map(|dir| {
+                        dir.filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                names.sort();
+                names.retain(|name| {
+                    patterns.iter().any(|pattern| {
+                        if !pattern.contains(['*', '?']) {
+                            return pattern == name;
+                        }
+                        glob_match(pattern, name) && (self.include_virtual || !is_virtual(name))
+                    })
+                });
+                names
+            }
+        }
+    }
+
+    fn render(&self, interfaces: &[&str], per_second: &PerSecond) -> String {
+        let (rx, tx) = (per_second.rx, per_second.tx);
+        let addresses = if ["ipv4", "ipv6", "ipv4_all", "ipv6_all"]
+            .iter()
+            .any(|name| self.format.uses(name))
+        {
+            ifaddrs::addresses().unwrap_or_default()
+        } else {
+            vec![]
+        };
+        let v4: Vec<String> = addresses
+            .iter()
+            .filter(|(name, address)| interfaces.contains(&name.as_str()) && address.is_ipv4())
+            .map(|(_, address)| address.to_string())
+            .collect();
+        let v6: Vec<String> = addresses
+            .iter()
+            .filter(|(name, address)| interfaces.contains(&name.as_str()) && address.is_ipv6())
+            .map(|(_, address)| address.to_string())
+            .collect();
+        let global_v6 = addresses.iter().find_map(|(name, address)| match address {
+            IpAddr::V6(v6) if interfaces.contains(&name.as_str()) && !is_link_local(v6) => {
+                Some(v6.to_string())
+            }
+            _ => None,
+        });
+
+        let highlight = |icon: &str, rate: f64| {
+            if rate > 1024.0 * 1024.0 {
+                format!(
+                    "<span foreground='blue'>{} <b>{}</b></span>",
+                    icon,
+                    self.units.format(rate)
+                )
+            } else {
+                format!("{} {}", icon, self.units.format(rate))
+            }
+        };
+
+        self.format.render(|name| match name {
+            "rx" => highlight(self.icons.icon(Icon::Download, 0.0), rx).into(),
+            "tx" => highlight(self.icons.icon(Icon::Upload, 0.0), tx).into(),
+            "rx_rate" => self.units.format(rx).into(),
+            "tx_rate" => self.units.format(tx).into(),
+            "rx_kib" => (rx / 1024.0).into(),
+            "tx_kib" => (tx / 1024.0).into(),
+            // Fractions of a packet per second still matter for errors
+            "rx_packets" => Value::Number(per_second.rx_packets, 1),
+            "tx_packets" => Value::Number(per_second.tx_packets, 1),
+            "rx_errors" => Value::Number(per_second.rx_errors, 1),
+            "tx_errors" => Value::Number(per_second.tx_errors, 1),
+            "today" | "today_rx" | "today_tx" | "month" | "month_rx" | "month_tx" => {
+                self.usage_total(interfaces, name).into()
+            }
+            "quota" => self
+                .usage
+                .as_ref()
+                .and_then(|usage| usage.check_quota(interfaces))
+                .unwrap_or(0.0)
+                .into(),
+            "rx_sparkline" => self.rx_sparkline.render().into(),
+            "tx_sparkline" => self.tx_sparkline.render().into(),
+            "rx_peak" => self.units.format(self.rx_peak.max()).into(),
+            "tx_peak" => self.units.format(self.tx_peak.max()).into(),
+            "ipv4" => v4.first().cloned().unwrap_or_default().into(),
+            "ipv6" => global_v6.clone().unwrap_or_default().into(),
+            "ipv4_all" => v4.join(" ").into(),
+            "ipv6_all" => v6.join(" ").into(),
+            "gateway" => Self::gateway(interfaces).into(),
+            "speed" | "duplex" => {
+                match interfaces
+                    .iter()
+                    .find_map(|interface| link_speed(interface))
+                {
+                    Some((speed, _)) if name == "speed" => format_speed(speed).into(),
+                    Some((_, duplex)) => duplex.into(),
+                    None => "".into(),
+                }
+            }
+            _ => interfaces.join("+").into(),
+        })
+    }
+
+    /// Data used for one of the `today` and `month` placeholders.
+    #[allow(clippy::cast_precision_loss)]
+    fn usage_total(&self, interfaces: &[&str], name: &str) -> String {
+        let (today, month) = match &self.usage {
+            Some(usage) => usage.totals(interfaces),
+            None => Default::default(),
+        };
+        let totals = if name.starts_with("today") {
+            today
+        } else {
+            month
+        };
+        let bytes = if name.ends_with("_rx") {
+            totals.rx
+        } else if name.ends_with("_tx") {
+            totals.tx
+        } else {
+            totals.rx + totals.tx
+        };
+        self.units.per_second(false).format(bytes as f64)
+    }
+
+    /// Gateway of the first of `interfaces` holding a default route.
+    fn gateway(interfaces: &[&str]) -> String {
+        route::default_routes()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|route| interfaces.contains(&route.interface.as_str()))
+            .map(|route| route.gateway.to_string())
+            .unwrap_or_default()
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn metrics(&self, interface: &str, stats: &NetworkStats, rx: f64, tx: f64) -> Vec<Metric> {
+        let rate = |direction, value| {
+            Metric::gauge(
+                "network_bytes_per_second",
+                "Network throughput in bytes per second",
+                value,
+            )
+            .label("interface", interface)
+            .label("direction", direction)
+        };
+        let counter = |name, help, direction, value: u64| {
+            Metric::counter(name, help, value as f64)
+                .label("interface", interface)
+                .label("direction", direction)
+        };
+
+        vec![
+            rate("rx", rx),
+            rate("tx", tx),
+            counter("network_bytes", "Bytes transferred", "rx", stats.rx_bytes),
+            counter("network_bytes", "Bytes transferred", "tx", stats.tx_bytes),
+            counter(
+                "network_packets",
+                "Packets transferred",
+                "rx",
+                stats.rx_packets,
+            ),
+            counter(
+                "network_packets",
+                "Packets transferred",
+                "tx",
+                stats.tx_packets,
+            ),
+            counter("network_errors", "Transfer errors", "rx", stats.rx_errors),
+            counter("network_errors", "Transfer errors", "tx", stats.tx_errors),
+        ]
     }
 
     fn get_network_stats(inteface: &str) -> io::Result<NetworkStats> {
@@ -136,3 +735,300 @@ fn value_from_file<T: std::str::FromStr>(path: &str) -> io::Result<T> {
             )
         })
 }
+
+impl Peak {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            values: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn push(&self, time: Instant, value: f64) {
+        let mut values = self.values.lock().unwrap();
+        while let Some((oldest, _)) = values.front() {
+            if time - *oldest <= self.window {
+                break;
+            }
+            values.pop_front();
+        }
+        values.push_back((time, value));
+    }
+
+    fn clear(&self) {
+        self.values.lock().unwrap().clear();
+    }
+
+    fn max(&self) -> f64 {
+        let values = self.values.lock().unwrap();
+        values.iter().map(|(_, value)| *value).fold(0.0, f64::max)
+    }
+}
+
+/// Exponential moving average with `alpha` as the weight of `value`.
+fn ema(alpha: f64, last: f64, value: f64) -> f64 {
+    alpha * value + (1.0 - alpha) * last
+}
+
+impl PerSecond {
+    fn add(&self, other: &PerSecond) -> PerSecond {
+        PerSecond {
+            rx: self.rx + other.rx,
+            tx: self.tx + other.tx,
+            rx_packets: self.rx_packets + other.rx_packets,
+            tx_packets: self.tx_packets + other.tx_packets,
+            rx_errors: self.rx_errors + other.rx_errors,
+            tx_errors: self.tx_errors + other.tx_errors,
+        }
+    }
+}
+
+/// Increase of a counter from `old` to `new`, `None` if it was reset.
+///
+/// Some drivers only keep 32 bit counters, a decrease is only taken as their
+/// wraparound when `old` was about to overflow and `new` just started over.
+/// Anything else, e.g. an interface created anew, is a reset.
+fn counter_delta(old: u64, new: u64) -> Option<u64> {
+    // 256 MiB, more than a 32 bit counter moves within a few seconds
+    const MARGIN: u64 = 1 << 28;
+    const MAX: u64 = u32::MAX as u64;
+
+    if new >= old {
+        return Some(new - old);
+    }
+    if old <= MAX && old > MAX - MARGIN && new < MARGIN {
+        return Some(MAX - old + new + 1);
+    }
+    None
+}
+
+/// State of the link from `operstate` and `carrier`, `None` if the interface
+/// is gone.
+fn link_state(interface: &str) -> Option<Link> {
+    let path = format!("/sys/class/net/{}/", interface);
+    let operstate = read_file(&(path.clone() + "operstate")).ok()?;
+
+    match operstate.trim() {
+        "dormant" => Some(Link::Dormant),
+        "lowerlayerdown" => Some(Link::NoCarrier),
+        // `carrier` can't be read while the interface is administratively down
+        state => match value_from_file::<u8>(&(path + "carrier")) {
+            Ok(0) => Some(Link::NoCarrier),
+            Err(_) if state == "down" => Some(Link::Down),
+            _ => Some(Link::Up),
+        },
+    }
+}
+
+/// Negotiated speed in Mb/s and duplex of a wired link, `None` for wireless
+/// and virtual interfaces or while the link is down.
+fn link_speed(interface: &str) -> Option<(u32, String)> {
+    let path = format!("/sys/class/net/{}/", interface);
+    // tun and the like report a made up speed of 10 Mb/s, only trust
+    // interfaces backed by a device
+    if !std::path::Path::new(&(path.clone() + "device")).exists() {
+        return None;
+    }
+    // -1 or unreadable without a negotiated link
+    let speed = value_from_file::<u32>(&(path.clone() + "speed")).ok()?;
+    if speed == 0 {
+        return None;
+    }
+    let duplex = read_file(&(path + "duplex")).unwrap_or_default();
+    Some((speed, duplex.trim().to_string()))
+}
+
+/// `100 Mb/s`, `1 Gb/s` or `2.5 Gb/s`.
+fn format_speed(mbps: u32) -> String {
+    if mbps >= 1000 {
+        format!("{} Gb/s", f64::from(mbps) / 1000.0)
+    } else {
+        format!("{} Mb/s", mbps)
+    }
+}
+
+/// `fe80::/10`
+fn is_link_local(address: &std::net::Ipv6Addr) -> bool {
+    address.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// Loopback, bridges, veth pairs and the like have no device behind them.
+fn is_virtual(interface: &str) -> bool {
+    std::path::Path::new("/sys/devices/virtual/net")
+        .join(interface)
+        .exists()
+}
+
+/// Shell style match of `name` against `pattern`, `*` and `?` as wildcards.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bn)) => {
+                    p = bp;
+                    n = bn + 1;
+                    backtrack = Some((bp, bn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_increase() {
+        assert_eq!(counter_delta(100, 150), Some(50));
+        assert_eq!(counter_delta(100, 100), Some(0));
+    }
+
+    #[test]
+    fn counter_wraps_at_32_bit() {
+        let max = u64::from(u32::MAX);
+        assert_eq!(counter_delta(max - 9, 5), Some(15));
+        assert_eq!(counter_delta(max, 0), Some(1));
+    }
+
+    #[test]
+    fn counter_reset_is_dropped() {
+        // An interface created anew restarts near 0
+        assert_eq!(counter_delta(3_000_000_000, 1_000), None);
+        assert_eq!(counter_delta(1 << 31, 0), None);
+        assert_eq!(counter_delta(50_000, 10), None);
+        // 64 bit counters don't wrap within a lifetime
+        assert_eq!(counter_delta(1 << 40, 10), None);
+    }
+
+    fn sample(rx_bytes: u64, time: Instant, wall: SystemTime) -> Sample {
+        Sample {
+            stats: NetworkStats {
+                rx_bytes,
+                ..NetworkStats::default()
+            },
+            time,
+            wall,
+            rx_rate: 0.0,
+            tx_rate: 0.0,
+        }
+    }
+
+    #[test]
+    fn smooths_shown_rates_only() {
+        let network = Network::new(Duration::from_secs(1), "eth0").rx_smoothing(0.5);
+        let (time, wall) = (Instant::now(), SystemTime::now());
+        let mut last = sample(0, time, wall);
+        last.rx_rate = 1000.0;
+
+        let second = Duration::from_secs(1);
+        let mut next = sample(3000, time + second, wall + second);
+        let (per_second, measured) = network.smoothed_rates(Some(&last), &mut next);
+        assert_eq!(measured.rx, 3000.0);
+        assert_eq!(per_second.rx, 2000.0);
+        assert_eq!(next.rx_rate, 2000.0);
+    }
+
+    #[test]
+    fn no_rate_after_suspend_even_when_smoothed() {
+        let network = Network::new(Duration::from_secs(1), "eth0").rx_smoothing(0.3);
+        let (time, wall) = (Instant::now(), SystemTime::now());
+        let mut last = sample(0, time, wall);
+        last.rx_rate = 1000.0;
+
+        // The monotonic clock stood still for an hour
+        let mut next = sample(
+            50_000,
+            time + Duration::from_secs(1),
+            wall + Duration::from_secs(3600),
+        );
+        let (per_second, measured) = network.smoothed_rates(Some(&last), &mut next);
+        assert_eq!(measured.rx, 0.0);
+        assert_eq!(per_second.rx, 0.0);
+        assert_eq!(next.rx_rate, 0.0);
+
+        let mut first = sample(50_000, time, wall);
+        assert_eq!(network.smoothed_rates(None, &mut first).0.rx, 0.0);
+    }
+
+    #[test]
+    fn moving_average() {
+        assert_eq!(ema(1.0, 500.0, 100.0), 100.0);
+        assert_eq!(ema(0.0, 500.0, 100.0), 500.0);
+        assert_eq!(ema(0.25, 500.0, 100.0), 400.0);
+    }
+
+    #[test]
+    fn peak_within_window() {
+        let peak = Peak::new(Duration::from_secs(10));
+        let start = Instant::now();
+        assert_eq!(peak.max(), 0.0);
+
+        peak.push(start, 300.0);
+        peak.push(start + Duration::from_secs(5), 100.0);
+        assert_eq!(peak.max(), 300.0);
+        // Still within the window at exactly 10 seconds
+        peak.push(start + Duration::from_secs(10), 50.0);
+        assert_eq!(peak.max(), 300.0);
+        peak.push(start + Duration::from_secs(11), 50.0);
+        assert_eq!(peak.max(), 100.0);
+
+        peak.clear();
+        assert_eq!(peak.max(), 0.0);
+    }
+
+    #[test]
+    fn glob_matches() {
+        assert!(glob_match("en*", "enp3s0"));
+        assert!(glob_match("en*", "en"));
+        assert!(glob_match("*0", "wlan0"));
+        assert!(glob_match("wl?s0", "wlps0"));
+        assert!(glob_match("a*b*c", "aXbYc"));
+        assert!(glob_match("a*b*c", "abbbc"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("wlan0**", "wlan0"));
+        assert!(glob_match("eth0", "eth0"));
+    }
+
+    #[test]
+    fn glob_non_matches() {
+        assert!(!glob_match("en*", "wlan0"));
+        assert!(!glob_match("*0", "wlan1"));
+        assert!(!glob_match("wl?s0", "wls0"));
+        assert!(!glob_match("a*b*c", "aXbYd"));
+        assert!(!glob_match("eth0", "eth01"));
+        assert!(!glob_match("?", ""));
+    }
+
+    #[test]
+    fn history_starts_over_on_switch() {
+        let network = Network::auto(Duration::from_secs(1));
+        network.follow("eth0");
+        network.rx_sparkline.push(2048.0);
+        network.tx_peak.push(Instant::now(), 1024.0);
+
+        // Same interface again
+        network.follow("eth0");
+        assert_eq!(network.rx_sparkline.render().chars().count(), 1);
+
+        network.follow("wlan0");
+        assert_eq!(network.rx_sparkline.render(), "");
+        assert_eq!(network.tx_peak.max(), 0.0);
+    }
+}
@@ -0,0 +1,574 @@
+//! Minimal nl80211 client over generic netlink, enough to read the current
+//! link of a wireless interface without `iw` or `iwgetid`.
+
+use std::convert::TryInto;
+use std::ffi::CString;
+use std::io;
+use std::mem;
+
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_DUMP: u16 = 0x300;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+/// Strips `NLA_F_NESTED` and `NLA_F_NET_BYTEORDER`
+const NLA_TYPE_MASK: u16 = 0x3fff;
+const NLMSG_HDRLEN: usize = 16;
+const GENL_HDRLEN: usize = 4;
+
+const NL80211_CMD_GET_STATION: u8 = 17;
+const NL80211_CMD_GET_SCAN: u8 = 32;
+const NL80211_ATTR_IFINDEX: u16 = 3;
+const NL80211_ATTR_MAC: u16 = 6;
+const NL80211_ATTR_STA_INFO: u16 = 21;
+const NL80211_ATTR_BSS: u16 = 47;
+const NL80211_BSS_BSSID: u16 = 1;
+const NL80211_BSS_FREQUENCY: u16 = 2;
+const NL80211_BSS_INFORMATION_ELEMENTS: u16 = 6;
+const NL80211_BSS_SIGNAL_MBM: u16 = 7;
+const NL80211_BSS_STATUS: u16 = 9;
+const NL80211_BSS_STATUS_ASSOCIATED: u32 = 1;
+const NL80211_BSS_STATUS_IBSS_JOINED: u32 = 2;
+const NL80211_STA_INFO_SIGNAL: u16 = 7;
+const NL80211_STA_INFO_TX_BITRATE: u16 = 8;
+const NL80211_STA_INFO_RX_BITRATE: u16 = 14;
+const NL80211_RATE_INFO_BITRATE: u16 = 1;
+const NL80211_RATE_INFO_BITRATE32: u16 = 5;
+/// Information element holding the SSID
+const WLAN_EID_SSID: u8 = 0;
+
+/// The network a wireless interface is associated with.
+#[derive(Debug, Clone)]
+pub(crate) struct Link {
+    pub ssid: String,
+    pub bssid: [u8; 6],
+    /// MHz
+    pub frequency: Option<u32>,
+    pub signal_dbm: Option<i32>,
+    /// Mb/s
+    pub tx_bitrate: Option<f64>,
+    pub rx_bitrate: Option<f64>,
+}
+
+impl Link {
+    /// `aa:bb:cc:dd:ee:ff`
+    pub fn bssid(&self) -> String {
+        let octets: Vec<String> = self.bssid.iter().map(|b| format!("{:02x}", b)).collect();
+        octets.join(":")
+    }
+
+    /// IEEE 802.11 channel number of `frequency`.
+    pub fn channel(&self) -> Option<u32> {
+        match self.frequency? {
+            2484 => Some(14),
+            f @ 2412..=2472 => Some((f - 2407) / 5),
+            f @ 5955..=7115 => Some((f - 5950) / 5),
+            f @ 5000..=5925 => Some((f - 5000) / 5),
+            _ => None,
+        }
+    }
+}
+
+/// A netlink socket with the nl80211 family resolved, kept open across
+/// queries. Its calls block for up to a second if the kernel doesn't answer.
+pub(crate) struct Client {
+    socket: Socket,
+    family: u16,
+}
+
+/// Current link of `interface` over a socket of its own, see `Client::link`.
+pub(crate) fn link(interface: &str) -> io::Result<Option<Link>> {
+    Client::open()?.link(interface)
+}
+
+impl Client {
+    pub fn open() -> io::Result<Self> {
+        let mut socket = Socket::open()?;
+        let family = socket.family("nl80211")?;
+        Ok(Self { socket, family })
+    }
+
+    /// Current link of `interface`, `None` while it isn't associated or
+    /// doesn't exist.
+    pub fn link(&mut self, interface: &str) -> io::Result<Option<Link>> {
+        let name = CString::new(interface)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+        // SAFETY: `name` is a valid C string
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            return Ok(None);
+        }
+        let index = index.to_ne_bytes();
+
+        let scan = self.socket.request(
+            self.family,
+            NL80211_CMD_GET_SCAN,
+            NLM_F_DUMP,
+            &[(NL80211_ATTR_IFINDEX, &index)],
+        )?;
+        let mut link = match scan.iter().find_map(|reply| associated(reply)) {
+            Some(link) => link,
+            None => return Ok(None),
+        };
+
+        // Signal and bitrates of our connection to the access point
+        let station = self.socket.request(
+            self.family,
+            NL80211_CMD_GET_STATION,
+            0,
+            &[
+                (NL80211_ATTR_IFINDEX, &index),
+                (NL80211_ATTR_MAC, &link.bssid),
+            ],
+        );
+        if let Some(reply) = station.ok().and_then(|replies| replies.into_iter().next()) {
+            station_info(&reply, &mut link);
+        }
+
+        Ok(Some(link))
+    }
+}
+
+/// Signal and bitrates from a `GET_STATION` reply.
+fn station_info(reply: &[u8], link: &mut Link) {
+    if let Some(info) = find(reply, NL80211_ATTR_STA_INFO) {
+        if let Some(signal) = find(info, NL80211_STA_INFO_SIGNAL).and_then(|s| s.first()) {
+            link.signal_dbm = Some(i32::from(*signal as i8));
+        }
+        link.tx_bitrate = find(info, NL80211_STA_INFO_TX_BITRATE).and_then(bitrate);
+        link.rx_bitrate = find(info, NL80211_STA_INFO_RX_BITRATE).and_then(bitrate);
+    }
+}
+
+/// The BSS of a scan result if we are associated with it.
+fn associated(reply: &[u8]) -> Option<Link> {
+    let bss = find(reply, NL80211_ATTR_BSS)?;
+    let status = u32_of(find(bss, NL80211_BSS_STATUS)?)?;
+    if status != NL80211_BSS_STATUS_ASSOCIATED && status != NL80211_BSS_STATUS_IBSS_JOINED {
+        return None;
+    }
+
+    let bssid = find(bss, NL80211_BSS_BSSID)?.get(..6)?.try_into().ok()?;
+    let ssid = find(bss, NL80211_BSS_INFORMATION_ELEMENTS)
+        .and_then(ssid_of)
+        .unwrap_or_default();
+    let signal_dbm = find(bss, NL80211_BSS_SIGNAL_MBM)
+        .and_then(u32_of)
+        .map(|mbm| mbm as i32 / 100);
+
+    Some(Link {
+        ssid,
+        bssid,
+        frequency: find(bss, NL80211_BSS_FREQUENCY).and_then(u32_of),
+        signal_dbm,
+        tx_bitrate: None,
+        rx_bitrate: None,
+    })
+}
+
+/// SSID from the information elements, each `id, len, data`.
+fn ssid_of(mut elements: &[u8]) -> Option<String> {
+    while elements.len() >= 2 {
+        let (id, len) = (elements[0], usize::from(elements[1]));
+        let data = elements.get(2..2 + len)?;
+        if id == WLAN_EID_SSID {
+            return Some(String::from_utf8_lossy(data).into_owned());
+        }
+        elements = &elements[2 + len..];
+    }
+    None
+}
+
+/// Mb/s from a nested `nl80211_rate_info`, given in 100 kb/s.
+fn bitrate(rate_info: &[u8]) -> Option<f64> {
+    let rate = match find(rate_info, NL80211_RATE_INFO_BITRATE32).and_then(u32_of) {
+        Some(rate) => rate,
+        None => u32::from(u16::from_ne_bytes(
+            find(rate_info, NL80211_RATE_INFO_BITRATE)?
+                .get(..2)?
+                .try_into()
+                .ok()?,
+        )),
+    };
+    Some(f64::from(rate) / 10.0)
+}
+
+fn u32_of(data: &[u8]) -> Option<u32> {
+    Some(u32::from_ne_bytes(data.get(..4)?.try_into().ok()?))
+}
+
+/// Payload of the first attribute of `kind` in a list of netlink attributes.
+fn find(mut attributes: &[u8], kind: u16) -> Option<&[u8]> {
+    while attributes.len() >= 4 {
+        let len = usize::from(u16::from_ne_bytes([attributes[0], attributes[1]]));
+        let this = u16::from_ne_bytes([attributes[2], attributes[3]]) & NLA_TYPE_MASK;
+        if len < 4 || len > attributes.len() {
+            return None;
+        }
+        if this == kind {
+            return Some(&attributes[4..len]);
+        }
+        attributes = &attributes[align(len).min(attributes.len())..];
+    }
+    None
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+struct Socket {
+    fd: libc::c_int,
+    seq: u32,
+}
+
+impl Socket {
+    fn open() -> io::Result<Self> {
+        // SAFETY: plain syscalls, the descriptor is owned by `Socket` and
+        // closed on drop
+        unsafe {
+            let fd = libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_GENERIC,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let socket = Self { fd, seq: 0 };
+
+            let mut addr: libc::sockaddr_nl = mem::zeroed();
+            addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            if libc::bind(
+                fd,
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            ) < 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+
+            // Never block the widget for long if the kernel doesn't answer
+            let timeout = libc::timeval {
+                tv_sec: 1,
+                tv_usec: 0,
+            };
+            if libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const libc::timeval as *const libc::c_void,
+                mem::size_of::<libc::timeval>() as libc::socklen_t,
+            ) < 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(socket)
+        }
+    }
+
+    /// Id of the generic netlink family `name`.
+    fn family(&mut self, name: &str) -> io::Result<u16> {
+        let name = format!("{}\0", name);
+        let replies = self.request(
+            GENL_ID_CTRL,
+            CTRL_CMD_GETFAMILY,
+            0,
+            &[(CTRL_ATTR_FAMILY_NAME, name.as_bytes())],
+        )?;
+        replies
+            .first()
+            .and_then(|reply| find(reply, CTRL_ATTR_FAMILY_ID))
+            .and_then(|id| Some(u16::from_ne_bytes(id.get(..2)?.try_into().ok()?)))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no family id"))
+    }
+
+    /// Send a generic netlink request and collect the attributes of every
+    /// reply, all parts of a dump with `NLM_F_DUMP`.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn request(
+        &mut self,
+        family: u16,
+        command: u8,
+        flags: u16,
+        attributes: &[(u16, &[u8])],
+    ) -> io::Result<Vec<Vec<u8>>> {
+        self.seq += 1;
+
+        let mut message = vec![];
+        message.extend_from_slice(&0_u32.to_ne_bytes());
+        message.extend_from_slice(&family.to_ne_bytes());
+        message.extend_from_slice(&(NLM_F_REQUEST | flags).to_ne_bytes());
+        message.extend_from_slice(&self.seq.to_ne_bytes());
+        message.extend_from_slice(&0_u32.to_ne_bytes());
+        message.extend_from_slice(&[command, 1, 0, 0]);
+        for (kind, payload) in attributes {
+            message.extend_from_slice(&((4 + payload.len()) as u16).to_ne_bytes());
+            message.extend_from_slice(&kind.to_ne_bytes());
+            message.extend_from_slice(payload);
+            message.resize(align(message.len()), 0);
+        }
+        let len = message.len() as u32;
+        message[..4].copy_from_slice(&len.to_ne_bytes());
+
+        // SAFETY: `message` is valid for its length
+        let sent = unsafe {
+            libc::send(
+                self.fd,
+                message.as_ptr() as *const libc::c_void,
+                message.len(),
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let dump = flags & NLM_F_DUMP != 0;
+        let mut replies = vec![];
+        let mut buf = vec![0_u8; 64 * 1024];
+        loop {
+            // SAFETY: `buf` is valid for its length
+            let received =
+                unsafe { libc::recv(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+            if received < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            if parse_replies(&buf[..received as usize], self.seq, dump, &mut replies)? {
+                return Ok(replies);
+            }
+        }
+    }
+}
+
+/// Collect the attributes of the replies to `seq` in a received buffer,
+/// `true` once the last one arrived.
+#[allow(clippy::cast_possible_wrap)]
+fn parse_replies(
+    mut data: &[u8],
+    seq: u32,
+    dump: bool,
+    replies: &mut Vec<Vec<u8>>,
+) -> io::Result<bool> {
+    while data.len() >= NLMSG_HDRLEN {
+        let len = u32_of(data).unwrap_or(0) as usize;
+        if len < NLMSG_HDRLEN || len > data.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated netlink message",
+            ));
+        }
+        let kind = u16::from_ne_bytes([data[4], data[5]]);
+        let this = u32_of(&data[8..]).unwrap_or(0);
+        let body = &data[NLMSG_HDRLEN..len];
+        data = &data[align(len).min(data.len())..];
+
+        if this != seq {
+            continue;
+        }
+        match kind {
+            NLMSG_DONE => return Ok(true),
+            NLMSG_ERROR => {
+                let code = u32_of(body).unwrap_or(0) as i32;
+                if code != 0 {
+                    return Err(io::Error::from_raw_os_error(-code));
+                }
+                return Ok(true);
+            }
+            _ => {
+                if let Some(attributes) = body.get(GENL_HDRLEN..) {
+                    replies.push(attributes.to_vec());
+                }
+                if !dump {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        // SAFETY: `fd` is owned by us and not used afterwards
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Attribute with its header and padding
+    #[allow(clippy::cast_possible_truncation)]
+    fn attribute(kind: u16, payload: &[u8]) -> Vec<u8> {
+        let mut attribute = vec![];
+        attribute.extend_from_slice(&((4 + payload.len()) as u16).to_ne_bytes());
+        attribute.extend_from_slice(&kind.to_ne_bytes());
+        attribute.extend_from_slice(payload);
+        attribute.resize(align(attribute.len()), 0);
+        attribute
+    }
+
+    fn nested(kind: u16, attributes: &[Vec<u8>]) -> Vec<u8> {
+        // NLA_F_NESTED
+        attribute(kind | 0x8000, &attributes.concat())
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn message(kind: u16, seq: u32, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![];
+        message.extend_from_slice(&((NLMSG_HDRLEN + body.len()) as u32).to_ne_bytes());
+        message.extend_from_slice(&kind.to_ne_bytes());
+        message.extend_from_slice(&0_u16.to_ne_bytes());
+        message.extend_from_slice(&seq.to_ne_bytes());
+        message.extend_from_slice(&0_u32.to_ne_bytes());
+        message.extend_from_slice(body);
+        message.resize(align(message.len()), 0);
+        message
+    }
+
+    fn scan_reply(elements: &[u8]) -> Vec<u8> {
+        nested(
+            NL80211_ATTR_BSS,
+            &[
+                attribute(NL80211_BSS_BSSID, &[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01]),
+                attribute(NL80211_BSS_FREQUENCY, &5180_u32.to_ne_bytes()),
+                attribute(NL80211_BSS_INFORMATION_ELEMENTS, elements),
+                attribute(NL80211_BSS_SIGNAL_MBM, &(-5600_i32).to_ne_bytes()),
+                attribute(
+                    NL80211_BSS_STATUS,
+                    &NL80211_BSS_STATUS_ASSOCIATED.to_ne_bytes(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn finds_nested_attributes_across_padding() {
+        // A 6 byte BSSID is padded to 8, a 1 byte value to 4
+        let attributes = [
+            attribute(1, &[7]),
+            nested(2, &[attribute(3, &[1, 2, 3, 4, 5, 6]), attribute(4, &[9])]),
+        ]
+        .concat();
+
+        assert_eq!(find(&attributes, 1), Some(&[7][..]));
+        let inner = find(&attributes, 2).unwrap();
+        assert_eq!(find(inner, 3), Some(&[1, 2, 3, 4, 5, 6][..]));
+        assert_eq!(find(inner, 4), Some(&[9][..]));
+        assert_eq!(find(inner, 5), None);
+    }
+
+    #[test]
+    fn stops_at_truncated_attribute() {
+        let mut attributes = attribute(1, &[1, 2, 3, 4]);
+        // Claims 16 bytes but only 8 follow
+        attributes.extend_from_slice(&16_u16.to_ne_bytes());
+        attributes.extend_from_slice(&2_u16.to_ne_bytes());
+        attributes.extend_from_slice(&[0; 4]);
+
+        assert_eq!(find(&attributes, 1), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(find(&attributes, 2), None);
+        assert_eq!(find(&attributes[..6], 1), None);
+    }
+
+    #[test]
+    fn reads_ssid_element() {
+        // A supported rates element before the SSID
+        let elements = [&[1, 2, 0x82, 0x84][..], &[0, 4], b"home"].concat();
+        assert_eq!(ssid_of(&elements).as_deref(), Some("home"));
+
+        // Hidden networks send a zero-length SSID
+        assert_eq!(ssid_of(&[0, 0, 1, 1, 0x82]).as_deref(), Some(""));
+        // Length beyond the data
+        assert_eq!(ssid_of(&[0, 8, b'a']), None);
+        assert_eq!(ssid_of(&[1, 1, 0x82]), None);
+    }
+
+    #[test]
+    fn reads_associated_bss() {
+        let elements = [&[0, 4][..], b"home"].concat();
+        let link = associated(&scan_reply(&elements)).unwrap();
+
+        assert_eq!(link.ssid, "home");
+        assert_eq!(link.bssid(), "aa:bb:cc:dd:ee:01");
+        assert_eq!(link.frequency, Some(5180));
+        assert_eq!(link.channel(), Some(36));
+        assert_eq!(link.signal_dbm, Some(-56));
+    }
+
+    #[test]
+    fn reads_16_and_32_bit_bitrates() {
+        let legacy = attribute(NL80211_RATE_INFO_BITRATE, &540_u16.to_ne_bytes());
+        assert_eq!(bitrate(&legacy), Some(54.0));
+
+        // The 32 bit rate wins, the 16 bit one saturates above 6.5 Gb/s
+        let both = [
+            attribute(NL80211_RATE_INFO_BITRATE, &u16::MAX.to_ne_bytes()),
+            attribute(NL80211_RATE_INFO_BITRATE32, &86_670_u32.to_ne_bytes()),
+        ]
+        .concat();
+        assert_eq!(bitrate(&both), Some(8667.0));
+        assert_eq!(bitrate(&[]), None);
+
+        let reply = nested(
+            NL80211_ATTR_STA_INFO,
+            &[
+                attribute(NL80211_STA_INFO_SIGNAL, &[(-61_i8) as u8]),
+                nested(NL80211_STA_INFO_TX_BITRATE, &[legacy]),
+            ],
+        );
+        let mut link = associated(&scan_reply(&[])).unwrap();
+        station_info(&reply, &mut link);
+        assert_eq!(link.signal_dbm, Some(-61));
+        assert_eq!(link.tx_bitrate, Some(54.0));
+        assert_eq!(link.rx_bitrate, None);
+    }
+
+    #[test]
+    fn collects_dump_until_done() {
+        let genl = [&[NL80211_CMD_GET_SCAN, 1, 0, 0][..], &attribute(1, &[7])].concat();
+        let data = [
+            message(0x1c, 4, &genl),
+            // Answer to an older request
+            message(0x1c, 3, &genl),
+            message(0x1c, 4, &genl),
+            message(NLMSG_DONE, 4, &[0; 4]),
+        ]
+        .concat();
+
+        let mut replies = vec![];
+        assert!(parse_replies(&data, 4, true, &mut replies).unwrap());
+        assert_eq!(replies, vec![attribute(1, &[7]); 2]);
+
+        // The rest of a dump is in the next buffer
+        let mut replies = vec![];
+        assert!(!parse_replies(&message(0x1c, 4, &genl), 4, true, &mut replies).unwrap());
+        assert_eq!(replies.len(), 1);
+    }
+
+    #[test]
+    fn reports_netlink_errors() {
+        // The error code is followed by the header of the failed request
+        let body = [&(-libc::ENODEV).to_ne_bytes()[..], &[0; NLMSG_HDRLEN]].concat();
+        let error =
+            parse_replies(&message(NLMSG_ERROR, 1, &body), 1, false, &mut vec![]).unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(libc::ENODEV));
+
+        // An error code of 0 acknowledges the request
+        let ack = [&0_i32.to_ne_bytes()[..], &[0; NLMSG_HDRLEN]].concat();
+        assert!(parse_replies(&message(NLMSG_ERROR, 1, &ack), 1, false, &mut vec![]).unwrap());
+
+        let truncated = &message(NLMSG_ERROR, 1, &body)[..NLMSG_HDRLEN + 2];
+        let mut header = truncated.to_vec();
+        header[..4].copy_from_slice(&64_u32.to_ne_bytes());
+        assert!(parse_replies(&header, 1, false, &mut vec![]).is_err());
+    }
+}
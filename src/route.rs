@@ -0,0 +1,98 @@
+use std::io;
+use std::net::Ipv4Addr;
+
+/// An IPv4 route from `/proc/net/route`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Route {
+    pub interface: String,
+    pub gateway: Ipv4Addr,
+    metric: u32,
+}
+
+/// The default route with the lowest metric, `None` while offline.
+pub(crate) fn default_route() -> io::Result<Option<Route>> {
+    Ok(preferred(default_routes()?))
+}
+
+/// The route the kernel picks among `routes`, the one with the lowest metric.
+fn preferred(routes: Vec<Route>) -> Option<Route> {
+    routes.into_iter().min_by_key(|route| route.metric)
+}
+
+/// Every default route that is up, one per uplink.
+pub(crate) fn default_routes() -> io::Result<Vec<Route>> {
+    let table = std::fs::read_to_string("/proc/net/route")?;
+    Ok(parse(&table))
+}
+
+/// Parse the default routes that are up, addresses are little endian hex.
+fn parse(table: &str) -> Vec<Route> {
+    const RTF_UP: u32 = 0x1;
+
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let hex = |i: usize| u32::from_str_radix(fields.get(i)?, 16).ok();
+            let (destination, gateway, flags, mask) = (hex(1)?, hex(2)?, hex(3)?, hex(7)?);
+            let metric = fields.get(6)?.parse().ok()?;
+
+            if destination != 0 || mask != 0 || flags & RTF_UP == 0 {
+                return None;
+            }
+            Some(Route {
+                interface: fields[0].to_string(),
+                gateway: Ipv4Addr::from(gateway.to_le_bytes()),
+                metric,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+wlan0\t00000000\t0100A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+eth0\t00000000\tFE01010A\t0003\t0\t0\t100\t00000000\t0\t0\t0
+eth0\t0001010A\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+wg0\t00000000\t00000000\t0001\t0\t0\t50\t00000080\t0\t0\t0
+usb0\t00000000\t012AA8C0\t0002\t0\t0\t10\t00000000\t0\t0\t0
+";
+
+    #[test]
+    fn parses_default_routes() {
+        let routes = parse(TABLE);
+        assert_eq!(
+            routes,
+            vec![
+                Route {
+                    interface: "wlan0".to_string(),
+                    gateway: Ipv4Addr::new(192, 168, 0, 1),
+                    metric: 600,
+                },
+                Route {
+                    interface: "eth0".to_string(),
+                    gateway: Ipv4Addr::new(10, 1, 1, 254),
+                    metric: 100,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lowest_metric_wins() {
+        let route = preferred(parse(TABLE)).unwrap();
+        assert_eq!(route.interface, "eth0");
+        assert_eq!(preferred(vec![]), None);
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        assert!(parse("").is_empty());
+        assert!(parse("Iface\nwlan0\t00000000\tnothex\t0003\n").is_empty());
+    }
+}
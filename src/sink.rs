@@ -0,0 +1,84 @@
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+
+/// Destination of a rendered bar.
+pub trait Sink: Send {
+    fn show(&mut self, text: &str);
+}
+
+/// Set the root window name, which is what dwm displays as its status.
+#[derive(Debug, Default)]
+pub struct XSetRoot {
+    display: Option<String>,
+}
+
+impl XSetRoot {
+    pub fn new() -> Self {
+        Self { display: None }
+    }
+
+    /// Target the root window of another X display or screen, e.g. `:0.1`.
+    pub fn on_display(display: &str) -> Self {
+        Self {
+            display: Some(display.to_string()),
+        }
+    }
+}
+
+impl Sink for XSetRoot {
+    fn show(&mut self, text: &str) {
+        let mut command = Command::new("xsetroot");
+        if let Some(display) = &self.display {
+            command.arg("-display").arg(display);
+        }
+
+        command
+            .arg("-name")
+            .arg(text)
+            .output()
+            .expect("failed to execute process");
+    }
+}
+
+/// Print every update as a line on stdout.
+#[derive(Debug, Default)]
+pub struct Stdout;
+
+impl Sink for Stdout {
+    fn show(&mut self, text: &str) {
+        println!("{}", text);
+    }
+}
+
+/// Feed every update as a line to the stdin of a long running command,
+/// e.g. one `lemonbar` per monitor.
+#[derive(Debug)]
+pub struct Pipe {
+    child: Child,
+}
+
+impl Pipe {
+    pub fn spawn(command: &str) -> std::io::Result<Self> {
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        Ok(Self { child })
+    }
+}
+
+impl Sink for Pipe {
+    fn show(&mut self, text: &str) {
+        let res = self
+            .child
+            .stdin
+            .as_mut()
+            .map(|stdin| writeln!(stdin, "{}", text));
+
+        if let Some(Err(e)) = res {
+            eprintln!("Failed to write to pipe: {}", e);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+#![allow(clippy::non_ascii_literal)]
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Scale {
+    /// Map `min..=max` to the lowest and highest bar, e.g. `0..=100` percent
+    Fixed(f64, f64),
+    /// Scale from 0 to the highest sample currently shown
+    Auto,
+}
+
+/// Ring buffer of the last samples of a value, rendered as `▁▂▃▅▇`.
+#[derive(Debug)]
+pub struct Sparkline {
+    samples: Mutex<VecDeque<f64>>,
+    length: usize,
+    scale: Scale,
+}
+
+impl Sparkline {
+    pub fn new(length: usize, scale: Scale) -> Self {
+        Self {
+            samples: Mutex::new(VecDeque::with_capacity(length)),
+            length,
+            scale,
+        }
+    }
+
+    pub(crate) fn push(&self, value: f64) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == self.length {
+            samples.pop_front();
+        }
+        if self.length > 0 {
+            samples.push_back(value);
+        }
+    }
+
+    /// Forget the samples, e.g. once they are about something else.
+    pub(crate) fn clear(&self) {
+        self.samples.lock().unwrap().clear();
+    }
+
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss
+    )]
+    pub(crate) fn render(&self) -> String {
+        let samples = self.samples.lock().unwrap();
+        let (min, max) = match self.scale {
+            Scale::Fixed(min, max) => (min, max),
+            Scale::Auto => (0.0, samples.iter().cloned().fold(0.0, f64::max)),
+        };
+
+        samples
+            .iter()
+            .map(|value| {
+                let ratio = if max > min {
+                    ((value - min) / (max - min)).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                BARS[(ratio * (BARS.len() - 1) as f64).round() as usize]
+            })
+            .collect()
+    }
+}
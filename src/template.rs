@@ -0,0 +1,286 @@
+use crate::Align;
+
+use std::fmt::{self, Display, Formatter};
+use unicode_width::UnicodeWidthStr;
+
+/// A format string with named placeholders, e.g. `"{icon} {percent:>3.0}%"`.
+///
+/// A placeholder is `{name}` or `{name:spec}` where `spec` is
+/// `[[fill]align][width][.precision]` like in `format!`, with `<`, `>` and `^`
+/// as alignments and the width in display columns. Use `{{` and `}}` for
+/// literal braces.
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Placeholder { name: String, spec: Spec },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Spec {
+    fill: char,
+    align: Option<Align>,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// Value substituted for a placeholder.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Text(String),
+    /// A number and the precision used when the placeholder doesn't set one
+    Number(f64, usize),
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Text(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Text(s.to_string())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Number(v, 0)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TemplateError(pub(crate) String);
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl Template {
+    /// Parse `template`, rejecting any placeholder not in `placeholders`.
+    pub fn parse(template: &str, placeholders: &[&str]) -> Result<Self, TemplateError> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => inner.push(c),
+                            None => {
+                                return Err(TemplateError(format!(
+                                    "unclosed placeholder `{{{}` in \"{}\"",
+                                    inner, template
+                                )))
+                            }
+                        }
+                    }
+
+                    let mut split = inner.splitn(2, ':');
+                    let name = split.next().unwrap_or("").trim().to_string();
+                    if !placeholders.contains(&name.as_str()) {
+                        return Err(TemplateError(format!(
+                            "unknown placeholder `{{{}}}` in \"{}\", expected one of: {}",
+                            name,
+                            template,
+                            placeholders.join(", ")
+                        )));
+                    }
+                    let spec = Spec::parse(split.next().unwrap_or("")).ok_or_else(|| {
+                        TemplateError(format!("invalid format spec in `{{{}}}`", inner))
+                    })?;
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder { name, spec });
+                }
+                '}' => return Err(TemplateError(format!("unmatched `}}` in \"{}\"", template))),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Whether `name` appears in the template, to skip expensive lookups.
+    pub fn uses(&self, name: &str) -> bool {
+        self.parts.iter().any(|part| match part {
+            Part::Placeholder { name: n, .. } => n == name,
+            Part::Literal(_) => false,
+        })
+    }
+
+    /// Render the template, asking `value` for every placeholder it contains.
+    pub fn render(&self, value: impl Fn(&str) -> Value) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => out.push_str(s),
+                Part::Placeholder { name, spec } => out.push_str(&spec.apply(value(name))),
+            }
+        }
+        out
+    }
+}
+
+impl Spec {
+    fn parse(spec: &str) -> Option<Self> {
+        let chars: Vec<char> = spec.chars().collect();
+        let to_align = |c: &char| match c {
+            '<' => Some(Align::Left),
+            '>' => Some(Align::Right),
+            '^' => Some(Align::Center),
+            _ => None,
+        };
+
+        let (fill, align, rest) = match chars.as_slice() {
+            [fill, align, rest @ ..] if to_align(align).is_some() => (*fill, to_align(align), rest),
+            [align, rest @ ..] if to_align(align).is_some() => (' ', to_align(align), rest),
+            rest => (' ', None, rest),
+        };
+
+        let rest: String = rest.iter().collect();
+        let mut split = rest.splitn(2, '.');
+        let width = match split.next() {
+            Some("") | None => 0,
+            Some(width) => width.parse().ok()?,
+        };
+        let precision = match split.next() {
+            Some(precision) => Some(precision.parse().ok()?),
+            None => None,
+        };
+
+        Some(Self {
+            fill,
+            align,
+            width,
+            precision,
+        })
+    }
+
+    fn apply(&self, value: Value) -> String {
+        // Numbers align right by default, like in `format!`
+        let (s, default_align) = match value {
+            Value::Text(s) => (s, Align::Left),
+            Value::Number(v, precision) => (
+                format!("{:.*}", self.precision.unwrap_or(precision), v),
+                Align::Right,
+            ),
+        };
+
+        // Wide characters such as CJK take two columns
+        let width = s.width();
+        if width >= self.width {
+            return s;
+        }
+
+        let pad = self.width - width;
+        let (left, right) = match self.align.unwrap_or(default_align) {
+            Align::Left => (0, pad),
+            Align::Right => (pad, 0),
+            Align::Center => (pad / 2, pad - pad / 2),
+        };
+
+        let fill = |n| self.fill.to_string().repeat(n);
+        format!("{}{}{}", fill(left), s, fill(right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLACEHOLDERS: &[&str] = &["icon", "percent", "name", "title"];
+
+    fn render(template: &str) -> String {
+        Template::parse(template, PLACEHOLDERS)
+            .unwrap()
+            .render(|name| match name {
+                "icon" => "I".into(),
+                "name" => "wlan0".into(),
+                "title" => "夜に駆ける".into(),
+                _ => Value::Number(42.25, 1),
+            })
+    }
+
+    #[test]
+    fn renders_placeholders_and_literals() {
+        assert_eq!(render("{icon} {percent}%"), "I 42.2%");
+        assert_eq!(render("{{{name}}}"), "{wlan0}");
+        assert_eq!(render("no placeholders"), "no placeholders");
+        assert_eq!(render(""), "");
+    }
+
+    #[test]
+    fn applies_width_alignment_and_precision() {
+        assert_eq!(render("{percent:.0}"), "42");
+        assert_eq!(render("{percent:.3}"), "42.250");
+        // Numbers align right and text left by default
+        assert_eq!(render("[{percent:6}]"), "[  42.2]");
+        assert_eq!(render("[{name:7}]"), "[wlan0  ]");
+        assert_eq!(render("[{name:>7}]"), "[  wlan0]");
+        assert_eq!(render("[{name:^8}]"), "[ wlan0  ]");
+        assert_eq!(render("[{percent:0>7.1}]"), "[00042.2]");
+        assert_eq!(render("[{percent:*<6.0}]"), "[42****]");
+        // Never truncated
+        assert_eq!(render("[{name:2}]"), "[wlan0]");
+    }
+
+    #[test]
+    fn pads_to_display_columns() {
+        assert_eq!(render("[{title:12}]"), "[夜に駆ける  ]");
+        assert_eq!(render("[{title:^13}]"), "[ 夜に駆ける  ]");
+        assert_eq!(render("[{title:8}]"), "[夜に駆ける]");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        let error = Template::parse("{icon} {temp}", PLACEHOLDERS).unwrap_err();
+        assert!(
+            error.0.contains("unknown placeholder `{temp}`"),
+            "{}",
+            error
+        );
+        assert!(error.0.contains("icon, percent, name, title"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert!(Template::parse("{icon", PLACEHOLDERS).is_err());
+        assert!(Template::parse("icon}", PLACEHOLDERS).is_err());
+        assert!(Template::parse("{percent:x}", PLACEHOLDERS).is_err());
+        assert!(Template::parse("{percent:.}", PLACEHOLDERS).is_err());
+    }
+
+    #[test]
+    fn reports_used_placeholders() {
+        let template = Template::parse("{icon} {{name}}", PLACEHOLDERS).unwrap();
+        assert!(template.uses("icon"));
+        assert!(!template.uses("name"));
+    }
+}
@@ -0,0 +1,257 @@
+use std::sync::Mutex;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Direction {
+    /// Higher values are worse, e.g. CPU usage
+    Ascending,
+    /// Lower values are worse, e.g. battery charge
+    Descending,
+}
+
+/// Colors for value ranges, shared by the widgets that highlight a value.
+///
+/// With `Ascending` stops the color of the highest stop at or below the value
+/// is used, with `Descending` the lowest stop at or above it. Level 0 is
+/// always the first stop given, which for `Descending` is the most critical.
+#[derive(Debug)]
+pub struct Thresholds {
+    stops: Vec<(f64, String)>,
+    direction: Direction,
+    hysteresis: f64,
+    gradient: bool,
+    last_level: Mutex<Option<usize>>,
+}
+
+impl Thresholds {
+    /// `stops` are `(value, color)` pairs sorted by value.
+    pub fn ascending(stops: &[(f64, &str)]) -> Self {
+        Self::new(stops, Direction::Ascending)
+    }
+
+    /// `stops` are `(value, color)` pairs sorted by value.
+    pub fn descending(stops: &[(f64, &str)]) -> Self {
+        Self::new(stops, Direction::Descending)
+    }
+
+    fn new(stops: &[(f64, &str)], direction: Direction) -> Self {
+        Self {
+            stops: stops
+                .iter()
+                .map(|(value, color)| (*value, (*color).to_string()))
+                .collect(),
+            direction,
+            hysteresis: 0.0,
+            gradient: false,
+            last_level: Mutex::new(None),
+        }
+    }
+
+    /// Only fall back to a less critical level once the value is past the
+    /// boundary by `hysteresis`, so the color doesn't flap around a stop.
+    pub fn hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Interpolate between the stop colors instead of stepping, only for
+    /// colors given as `#RRGGBB`.
+    pub fn gradient(mut self, gradient: bool) -> Self {
+        self.gradient = gradient;
+        self
+    }
+
+    /// Index of the stop `value` falls in, `None` below every stop.
+    ///
+    /// Each call is taken as a new sample for the hysteresis, call it once
+    /// per sample and use `level_color` to get the color as well.
+    pub fn level(&self, value: f64) -> Option<usize> {
+        let mut last = self.last_level.lock().unwrap();
+
+        let raw = self.raw_level(value);
+        let level = if self.severity(raw) < self.severity(*last) {
+            let calmed = match self.direction {
+                Direction::Ascending => self.raw_level(value + self.hysteresis),
+                Direction::Descending => self.raw_level(value - self.hysteresis),
+            };
+            if self.severity(calmed) < self.severity(*last) {
+                calmed
+            } else {
+                *last
+            }
+        } else {
+            raw
+        };
+
+        *last = level;
+        level
+    }
+
+    /// Color of a new sample `value`, see `level`.
+    pub fn color(&self, value: f64) -> Option<String> {
+        self.level_color(self.level(value), value)
+    }
+
+    /// Color of `level` as returned by `level` for `value`.
+    pub fn level_color(&self, level: Option<usize>, value: f64) -> Option<String> {
+        let level = level?;
+        if self.gradient {
+            // A level held by the hysteresis keeps its colors
+            if let Some(color) = self.interpolate(self.clamp_to(level, value)) {
+                return Some(color);
+            }
+        }
+        Some(self.stops.get(level)?.1.clone())
+    }
+
+    /// `value` moved into the range of the stops of `level`.
+    fn clamp_to(&self, level: usize, value: f64) -> f64 {
+        let (low, high) = match self.direction {
+            Direction::Ascending => (
+                self.stops.get(level).map(|stop| stop.0),
+                self.stops.get(level + 1).map(|stop| stop.0),
+            ),
+            Direction::Descending => (
+                level
+                    .checked_sub(1)
+                    .and_then(|below| self.stops.get(below))
+                    .map(|stop| stop.0),
+                self.stops.get(level).map(|stop| stop.0),
+            ),
+        };
+        let value = low.map_or(value, |low| value.max(low));
+        high.map_or(value, |high| value.min(high))
+    }
+
+    fn raw_level(&self, value: f64) -> Option<usize> {
+        match self.direction {
+            Direction::Ascending => self.stops.iter().rposition(|(stop, _)| value >= *stop),
+            Direction::Descending => self.stops.iter().position(|(stop, _)| value <= *stop),
+        }
+    }
+
+    /// 0 for no level, growing with how critical the level is
+    fn severity(&self, level: Option<usize>) -> usize {
+        match (level, self.direction) {
+            (None, _) => 0,
+            (Some(i), Direction::Ascending) => i + 1,
+            (Some(i), Direction::Descending) => self.stops.len() - i,
+        }
+    }
+
+    fn interpolate(&self, value: f64) -> Option<String> {
+        let first = self.stops.first()?;
+        let last = self.stops.last()?;
+        if value <= first.0 {
+            return parse_hex(&first.1).map(|_| first.1.clone());
+        }
+        if value >= last.0 {
+            return parse_hex(&last.1).map(|_| last.1.clone());
+        }
+
+        let pair = self.stops.windows(2).find(|w| value < w[1].0)?;
+        let (from, to) = (&pair[0], &pair[1]);
+        let (a, b) = (parse_hex(&from.1)?, parse_hex(&to.1)?);
+        let t = (value - from.0) / (to.0 - from.0);
+
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let mix = |a: u8, b: u8| (f64::from(a) + (f64::from(b) - f64::from(a)) * t).round() as u8;
+        Some(format!(
+            "#{:02X}{:02X}{:02X}",
+            mix(a[0], b[0]),
+            mix(a[1], b[1]),
+            mix(a[2], b[2])
+        ))
+    }
+}
+
+fn parse_hex(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascending_levels() {
+        let thresholds = Thresholds::ascending(&[(50.0, "yellow"), (80.0, "red")]);
+        assert_eq!(thresholds.level(10.0), None);
+        assert_eq!(thresholds.level(50.0), Some(0));
+        assert_eq!(thresholds.level(79.9), Some(0));
+        assert_eq!(thresholds.color(95.0).as_deref(), Some("red"));
+    }
+
+    #[test]
+    fn descending_levels() {
+        let thresholds = Thresholds::descending(&[(10.0, "red"), (25.0, "yellow")]);
+        assert_eq!(thresholds.level(50.0), None);
+        assert_eq!(thresholds.level(25.0), Some(1));
+        assert_eq!(thresholds.color(5.0).as_deref(), Some("red"));
+    }
+
+    #[test]
+    fn hysteresis_does_not_flap_at_a_stop() {
+        let thresholds = Thresholds::ascending(&[(50.0, "yellow"), (80.0, "red")]).hysteresis(5.0);
+        assert_eq!(thresholds.level(50.5), Some(0));
+        for value in &[49.5, 50.5, 46.0, 50.1, 45.5] {
+            assert_eq!(thresholds.level(*value), Some(0), "at {}", value);
+        }
+        assert_eq!(thresholds.level(44.0), None);
+        // Getting worse is never held back
+        assert_eq!(thresholds.level(80.0), Some(1));
+        assert_eq!(thresholds.level(77.0), Some(1));
+        assert_eq!(thresholds.level(74.0), Some(0));
+    }
+
+    #[test]
+    fn descending_hysteresis() {
+        let thresholds = Thresholds::descending(&[(25.0, "red")]).hysteresis(5.0);
+        assert_eq!(thresholds.level(24.0), Some(0));
+        assert_eq!(thresholds.level(29.0), Some(0));
+        assert_eq!(thresholds.level(31.0), None);
+    }
+
+    #[test]
+    fn level_color_leaves_the_hysteresis_alone() {
+        let thresholds = Thresholds::ascending(&[(50.0, "yellow")]).hysteresis(5.0);
+        let level = thresholds.level(51.0);
+        assert_eq!(
+            thresholds.level_color(level, 51.0).as_deref(),
+            Some("yellow")
+        );
+        assert_eq!(thresholds.level_color(None, 51.0), None);
+
+        // Still the one sample above, so 48 is held
+        assert_eq!(thresholds.level(48.0), Some(0));
+    }
+
+    #[test]
+    fn gradient_between_stops() {
+        let thresholds =
+            Thresholds::ascending(&[(0.0, "#000000"), (100.0, "#FF0000")]).gradient(true);
+        assert_eq!(thresholds.color(50.0).as_deref(), Some("#800000"));
+        assert_eq!(thresholds.color(150.0).as_deref(), Some("#FF0000"));
+
+        // Colors that aren't hex step instead
+        let thresholds = Thresholds::ascending(&[(0.0, "green"), (100.0, "red")]).gradient(true);
+        assert_eq!(thresholds.color(50.0).as_deref(), Some("green"));
+    }
+
+    #[test]
+    fn gradient_keeps_the_held_level() {
+        let thresholds =
+            Thresholds::ascending(&[(0.0, "#000000"), (50.0, "#808080"), (100.0, "#FFFFFF")])
+                .gradient(true)
+                .hysteresis(10.0);
+
+        assert_eq!(thresholds.color(75.0).as_deref(), Some("#C0C0C0"));
+        // Held at the level of the 50 stop, not darker
+        assert_eq!(thresholds.color(45.0).as_deref(), Some("#808080"));
+        assert_eq!(thresholds.color(30.0).as_deref(), Some("#4D4D4D"));
+    }
+}
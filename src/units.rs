@@ -0,0 +1,174 @@
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Unit {
+    Bytes,
+    Bits,
+}
+
+/// `Si` scales by 1000 (kB, MB), `Iec` by 1024 (KiB, MiB).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Prefix {
+    Si,
+    Iec,
+}
+
+/// Human readable formatting of sizes and rates, e.g. `1.2 MiB/s`.
+///
+/// Values are always given in bytes and converted to bits when needed.
+#[derive(Debug, Copy, Clone)]
+pub struct Units {
+    unit: Unit,
+    prefix: Prefix,
+    precision: usize,
+    fixed_width: bool,
+    per_second: bool,
+}
+
+const SI: [&str; 6] = ["", "k", "M", "G", "T", "P"];
+const IEC: [&str; 6] = ["", "Ki", "Mi", "Gi", "Ti", "Pi"];
+
+impl Units {
+    /// Bytes with IEC prefixes and one decimal.
+    pub fn bytes() -> Self {
+        Self {
+            unit: Unit::Bytes,
+            prefix: Prefix::Iec,
+            precision: 1,
+            fixed_width: false,
+            per_second: false,
+        }
+    }
+
+    /// Bits with SI prefixes and one decimal, as network speeds are usually
+    /// given.
+    pub fn bits() -> Self {
+        Self {
+            unit: Unit::Bits,
+            prefix: Prefix::Si,
+            ..Self::bytes()
+        }
+    }
+
+    pub fn prefix(mut self, prefix: Prefix) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Pad the number and the unit so every value has the same width.
+    pub fn fixed_width(mut self, fixed_width: bool) -> Self {
+        self.fixed_width = fixed_width;
+        self
+    }
+
+    /// Append `/s`.
+    pub fn per_second(mut self, per_second: bool) -> Self {
+        self.per_second = per_second;
+        self
+    }
+
+    pub fn format(&self, bytes: f64) -> String {
+        let (mut value, symbol) = match self.unit {
+            Unit::Bytes => (bytes, "B"),
+            Unit::Bits => (bytes * 8.0, "b"),
+        };
+        let (base, prefixes) = match self.prefix {
+            Prefix::Si => (1000.0, SI),
+            Prefix::Iec => (1024.0, IEC),
+        };
+
+        // There is no such thing as a fraction of a byte
+        let precision_at = |scale| if scale == 0 { 0 } else { self.precision };
+
+        let mut scale = 0;
+        while value.abs() >= base && scale < prefixes.len() - 1 {
+            value /= base;
+            scale += 1;
+        }
+        // Rounding 1023.96 KiB would show 1024.0 KiB, make it 1.0 MiB
+        if scale < prefixes.len() - 1 {
+            let shown: f64 = format!("{:.*}", precision_at(scale), value)
+                .parse()
+                .unwrap_or(value);
+            if shown.abs() >= base {
+                value /= base;
+                scale += 1;
+            }
+        }
+        let precision = precision_at(scale);
+        let per_second = if self.per_second { "/s" } else { "" };
+        let unit = format!("{}{}{}", prefixes[scale], symbol, per_second);
+
+        if self.fixed_width {
+            // At most 4 digits before the point, e.g. `1023.9 KiB`
+            let width = 4 + if self.precision > 0 {
+                self.precision + 1
+            } else {
+                0
+            };
+            let unit_width = prefixes[prefixes.len() - 1].len() + symbol.len() + per_second.len();
+            format!(
+                "{:>width$.precision$} {:<unit_width$}",
+                value,
+                unit,
+                width = width,
+                precision = precision,
+                unit_width = unit_width
+            )
+        } else {
+            format!("{:.*} {}", precision, value, unit)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_at_the_boundaries() {
+        let units = Units::bytes();
+        assert_eq!(units.format(0.0), "0 B");
+        assert_eq!(units.format(1023.0), "1023 B");
+        assert_eq!(units.format(1023.6), "1.0 KiB");
+        assert_eq!(units.format(1024.0), "1.0 KiB");
+        assert_eq!(units.format(1536.0), "1.5 KiB");
+        assert_eq!(units.format(1023.94 * 1024.0), "1023.9 KiB");
+        assert_eq!(units.format(1023.96 * 1024.0), "1.0 MiB");
+        assert_eq!(units.format(1024.0 * 1024.0), "1.0 MiB");
+    }
+
+    #[test]
+    fn largest_prefix_keeps_growing() {
+        let pib = 1024_f64.powi(5);
+        assert_eq!(Units::bytes().format(2048.0 * pib), "2048.0 PiB");
+    }
+
+    #[test]
+    fn bits_and_rates() {
+        let units = Units::bits().per_second(true);
+        assert_eq!(units.format(124.0), "992 b/s");
+        assert_eq!(units.format(125.0), "1.0 kb/s");
+        // 999.96 kb/s
+        assert_eq!(units.format(124_995.0), "1.0 Mb/s");
+        assert_eq!(
+            Units::bytes().prefix(Prefix::Si).format(999_960.0),
+            "1.0 MB"
+        );
+        assert_eq!(Units::bytes().precision(0).format(1023.6 * 1024.0), "1 MiB");
+    }
+
+    #[test]
+    fn fixed_width() {
+        let units = Units::bytes().fixed_width(true).per_second(true);
+        assert_eq!(units.format(12.0), "    12 B/s  ");
+        assert_eq!(units.format(1023.9 * 1024.0), "1023.9 KiB/s");
+        assert_eq!(
+            units.format(5.0).len(),
+            units.format(5.5 * 1024.0 * 1024.0).len()
+        );
+    }
+}
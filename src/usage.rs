@@ -0,0 +1,384 @@
+use crate::history::data_dir;
+use crate::wifi;
+
+use chrono::Local;
+use notify_rust::{Notification, NotificationUrgency, Timeout};
+use smol::Task;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often the totals are synced to disk, they are written after every
+/// sample that changed them
+const SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Daily and monthly data usage of `Network`, kept on disk across restarts.
+///
+/// Each line of the file is `period,key,rx,tx` where `period` is a day
+/// (`2020-06-01`) or a month (`2020-06`), `key` the interface or with
+/// `per_ssid` the SSID of the wireless network, and `rx`, `tx` are bytes.
+/// Quota notifications already sent are kept as `period,notified,level`.
+/// Only the current month is kept.
+#[derive(Debug)]
+pub struct Usage {
+    path: PathBuf,
+    per_ssid: bool,
+    daily_quota: Option<u64>,
+    monthly_quota: Option<u64>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    loaded: bool,
+    totals: HashMap<(String, String), Totals>,
+    /// Key each interface was last counted under
+    keys: HashMap<String, String>,
+    /// SSID of each wireless interface and its `carrier_changes` when it was
+    /// looked up
+    ssids: HashMap<String, (Option<u64>, Option<String>)>,
+    /// Changed since the last write
+    dirty: bool,
+    last_sync: Option<Instant>,
+    /// Highest quota notification sent per period, 80 or 100
+    notified: HashMap<String, u8>,
+}
+
+/// Bytes received and transmitted.
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct Totals {
+    pub rx: u64,
+    pub tx: u64,
+}
+
+impl Usage {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            per_ssid: false,
+            daily_quota: None,
+            monthly_quota: None,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// `$XDG_DATA_HOME/barr/usage.csv`, falling back to `~/.local/share`.
+    pub fn default_path() -> PathBuf {
+        data_dir().join("usage.csv")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Count wireless interfaces per SSID, e.g. to track a tethered phone.
+    pub fn per_ssid(mut self, per_ssid: bool) -> Self {
+        self.per_ssid = per_ssid;
+        self
+    }
+
+    /// Bytes per day, the widget turns red once used up.
+    pub fn daily_quota(mut self, bytes: u64) -> Self {
+        self.daily_quota = Some(bytes);
+        self
+    }
+
+    /// Bytes per calendar month, the widget turns red once used up.
+    pub fn monthly_quota(mut self, bytes: u64) -> Self {
+        self.monthly_quota = Some(bytes);
+        self
+    }
+
+    /// Count `rx` and `tx` bytes transferred on `interface`.
+    pub(crate) fn add(&self, interface: &str, rx: u64, tx: u64) {
+        let mut state = self.state.lock().unwrap();
+        self.load(&mut state);
+
+        let key = match self.ssid(&mut state, interface) {
+            Some(ssid) => ssid,
+            None => interface.to_string(),
+        };
+        for period in &periods() {
+            let totals = state
+                .totals
+                .entry((period.clone(), key.clone()))
+                .or_default();
+            totals.rx += rx;
+            totals.tx += tx;
+        }
+        state.keys.insert(interface.to_string(), key);
+
+        if rx + tx > 0 {
+            state.dirty = true;
+        }
+    }
+
+    /// SSID to count a wireless `interface` under with `per_ssid`, only
+    /// looked up again once its link changed.
+    fn ssid(&self, state: &mut State, interface: &str) -> Option<String> {
+        if !self.per_ssid || !wifi::is_wireless(interface) {
+            return None;
+        }
+
+        let changes =
+            std::fs::read_to_string(format!("/sys/class/net/{}/carrier_changes", interface))
+                .ok()
+                .and_then(|changes| changes.trim().parse().ok());
+        match state.ssids.get(interface) {
+            Some((cached, ssid)) if *cached == changes => ssid.clone(),
+            _ => {
+                let ssid = wifi::ssid(interface);
+                state
+                    .ssids
+                    .insert(interface.to_string(), (changes, ssid.clone()));
+                ssid
+            }
+        }
+    }
+
+    /// Totals of today and of this month over `interfaces`.
+    pub(crate) fn totals(&self, interfaces: &[&str]) -> (Totals, Totals) {
+        let mut state = self.state.lock().unwrap();
+        self.load(&mut state);
+        let [day, month] = periods();
+
+        // Counted under their own name until an SSID is known
+        let mut keys: Vec<String> = interfaces
+            .iter()
+            .map(|interface| {
+                state
+                    .keys
+                    .get(*interface)
+                    .cloned()
+                    .unwrap_or_else(|| (*interface).to_string())
+            })
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        let sum = |period: &String| {
+            keys.iter().fold(Totals::default(), |sum, key| {
+                let totals = state
+                    .totals
+                    .get(&(period.clone(), key.clone()))
+                    .copied()
+                    .unwrap_or_default();
+                Totals {
+                    rx: sum.rx + totals.rx,
+                    tx: sum.tx + totals.tx,
+                }
+            })
+        };
+        (sum(&day), sum(&month))
+    }
+
+    /// Percentage of the most used up quota over `interfaces`, notifying
+    /// once at 80% and at 100% per day or month.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn check_quota(&self, interfaces: &[&str]) -> Option<f64> {
+        let (day, month) = self.totals(interfaces);
+        let [day_period, month_period] = periods();
+
+        let mut most_used: Option<(f64, String, &str)> = None;
+        for (quota, totals, period, name) in &[
+            (self.daily_quota, day, day_period, "daily"),
+            (self.monthly_quota, month, month_period, "monthly"),
+        ] {
+            if let Some(quota) = quota {
+                let percent = (totals.rx + totals.tx) as f64 / *quota as f64 * 100.0;
+                let more = match &most_used {
+                    Some((most, _, _)) => percent > *most,
+                    None => true,
+                };
+                if more {
+                    most_used = Some((percent, period.clone(), name));
+                }
+            }
+        }
+
+        let (percent, period, name) = most_used?;
+        let level = if percent >= 100.0 {
+            100
+        } else if percent >= 80.0 {
+            80
+        } else {
+            return Some(percent);
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let notified = state.notified.entry(period).or_insert(0);
+        if *notified < level {
+            *notified = level;
+            Self::notify_quota(name, level);
+            state.dirty = true;
+        }
+        Some(percent)
+    }
+
+    fn load(&self, state: &mut State) {
+        if state.loaded {
+            return;
+        }
+        state.loaded = true;
+
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => {
+                eprintln!("Failed to read data usage from {:?}: {}", self.path, e);
+                return;
+            }
+        };
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split(',').collect();
+            match fields.as_slice() {
+                [period, key, rx, tx] => {
+                    if let (Ok(rx), Ok(tx)) = (rx.parse(), tx.parse()) {
+                        state.totals.insert(
+                            ((*period).to_string(), (*key).to_string()),
+                            Totals { rx, tx },
+                        );
+                    }
+                }
+                [period, "notified", level] => {
+                    if let Ok(level) = level.parse() {
+                        state.notified.insert((*period).to_string(), level);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Write the totals if they changed, syncing them to disk once in a
+    /// while. The file is written off the executor so a slow disk never holds
+    /// up the bars.
+    pub(crate) async fn flush(&self) {
+        let (lines, sync) = {
+            let mut state = self.state.lock().unwrap();
+            if !state.dirty {
+                return;
+            }
+            state.dirty = false;
+
+            let sync = match state.last_sync {
+                Some(last_sync) => last_sync.elapsed() >= SYNC_INTERVAL,
+                None => true,
+            };
+            if sync {
+                state.last_sync = Some(Instant::now());
+            }
+            (Self::lines(&state), sync)
+        };
+
+        let path = self.path.clone();
+        if let Err(e) = Task::blocking(async move { write(&path, &lines, sync) }).await {
+            eprintln!("Failed to save data usage to {:?}: {}", self.path, e);
+        }
+    }
+
+    /// Lines of the file, days and months before this month are dropped.
+    fn lines(state: &State) -> Vec<String> {
+        let [_, month] = periods();
+        let mut lines: Vec<String> = state
+            .totals
+            .iter()
+            .filter(|((period, _), _)| *period >= month)
+            .map(|((period, key), totals)| {
+                format!(
+                    "{},{},{},{}",
+                    period,
+                    key.replace([',', '\n'], "_"),
+                    totals.rx,
+                    totals.tx
+                )
+            })
+            .collect();
+        lines.extend(
+            state
+                .notified
+                .iter()
+                .filter(|(period, _)| **period >= month)
+                .map(|(period, level)| format!("{},notified,{}", period, level)),
+        );
+        lines.sort();
+        lines
+    }
+
+    fn notify_quota(name: &str, level: u8) {
+        let (summary, urgency) = if level >= 100 {
+            (
+                format!("Network {} quota used up", name),
+                NotificationUrgency::Critical,
+            )
+        } else {
+            (
+                format!("Network {} quota at {}%", name, level),
+                NotificationUrgency::Normal,
+            )
+        };
+        let res = Notification::new()
+            .summary(&summary)
+            .timeout(Timeout::Milliseconds(45 * 1000))
+            .hint(notify_rust::NotificationHint::Urgency(urgency))
+            .show();
+
+        if res.is_err() {
+            eprintln!("Failed create to notification");
+        }
+    }
+}
+
+fn write(path: &Path, lines: &[String], sync: bool) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // Write to a temporary file first so a crash never truncates the totals
+    let tmp = path.with_extension("csv.tmp");
+    let mut file = fs::File::create(&tmp)?;
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    if sync {
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+/// Today and this month in local time, e.g. `["2020-06-01", "2020-06"]`.
+fn periods() -> [String; 2] {
+    let now = Local::now();
+    [
+        now.format("%Y-%m-%d").to_string(),
+        now.format("%Y-%m").to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kept_across_restarts() {
+        let path = std::env::temp_dir().join(format!("barr-usage-{}.csv", std::process::id()));
+        let usage = Usage::new(&path).daily_quota(1000);
+        usage.add("eth0", 300, 400);
+        usage.add("eth0", 200, 0);
+        assert_eq!(usage.check_quota(&["eth0"]), Some(90.0));
+        smol::run(usage.flush());
+
+        let usage = Usage::new(&path).daily_quota(1000);
+        let (today, month) = usage.totals(&["eth0"]);
+        assert_eq!((today.rx, today.tx), (500, 400));
+        assert_eq!((month.rx, month.tx), (500, 400));
+        let [day, _] = periods();
+        assert_eq!(usage.state.lock().unwrap().notified.get(&day), Some(&80));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{Icon, IconPack, Metric, Template, TemplateError, Widget, WidgetOutput, WidgetState};
+use async_trait::async_trait;
+use smol::Task;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Active VPN and tunnel interfaces: WireGuard, tun/tap and interfaces without
+/// a link layer (type `none`).
+///
+/// Format placeholders: `{icon}`, `{vpns}` (names, for WireGuard with the age
+/// of the latest handshake if `wg show` may read it), `{count}`.
+///
+/// Shows `No VPN` in the `disconnected` state when there is none, and turns
+/// red in the `down` state while one of the `required` VPNs isn't up.
+#[derive(Debug)]
+pub struct Vpn {
+    interval: Duration,
+    icons: IconPack,
+    format: Template,
+    required: Vec<String>,
+}
+
+const PLACEHOLDERS: &[&str] = &["icon", "vpns", "count"];
+
+/// `ARPHRD_NONE`, e.g. WireGuard and tun devices
+const TYPE_NONE: &str = "65534";
+const IFF_UP: u32 = 0x1;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Kind {
+    WireGuard,
+    Tunnel,
+}
+
+struct Tunnel {
+    interface: String,
+    kind: Kind,
+    /// Time since the latest handshake of any peer, WireGuard only
+    handshake: Option<Option<Duration>>,
+}
+
+#[async_trait]
+impl Widget for Vpn {
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    async fn get_output(&self) -> WidgetOutput {
+        // `wg show` is a process of its own, keep it off the executor
+        let tunnels = Task::blocking(async { Self::tunnels() }).await;
+
+        let mut metrics = vec![];
+        for tunnel in &tunnels {
+            metrics.push(
+                Metric::gauge("vpn_up", "Whether the VPN interface is up", 1.0)
+                    .label("interface", tunnel.interface.as_str())
+                    .label(
+                        "type",
+                        match tunnel.kind {
+                            Kind::WireGuard => "wireguard",
+                            Kind::Tunnel => "tunnel",
+                        },
+                    ),
+            );
+            if let Some(Some(age)) = tunnel.handshake {
+                metrics.push(
+                    Metric::gauge(
+                        "vpn_handshake_age_seconds",
+                        "Seconds since the latest WireGuard handshake",
+                        age.as_secs() as f64,
+                    )
+                    .label("interface", tunnel.interface.as_str()),
+                );
+            }
+        }
+
+        let down: Vec<&str> = self
+            .required
+            .iter()
+            .filter(|name| !tunnels.iter().any(|t| &t.interface == *name))
+            .map(String::as_str)
+            .collect();
+        if !down.is_empty() {
+            return WidgetOutput {
+                text: format!(
+                    "<span foreground='red'><b>{} down</b></span>",
+                    down.join(" ")
+                ),
+                use_default_foreground: false,
+                use_default_background: true,
+                state: WidgetState::State("down"),
+                metrics,
+            };
+        }
+        if tunnels.is_empty() {
+            return WidgetOutput {
+                text: "<span foreground='grey'>No VPN</span>".to_string(),
+                use_default_foreground: true,
+                use_default_background: true,
+                state: WidgetState::State("disconnected"),
+                metrics,
+            };
+        }
+
+        let vpns: Vec<String> = tunnels
+            .iter()
+            .map(|tunnel| match tunnel.handshake {
+                Some(Some(age)) => format!("{} {}", tunnel.interface, format_age(age)),
+                Some(None) => format!("{} no handshake", tunnel.interface),
+                None => tunnel.interface.clone(),
+            })
+            .collect();
+
+        WidgetOutput {
+            text: self.format.render(|name| match name {
+                "icon" => self.icons.icon(Icon::Vpn, 0.0).into(),
+                "vpns" => vpns.join(" | ").into(),
+                _ => (tunnels.len() as f64).into(),
+            }),
+            use_default_foreground: true,
+            use_default_background: true,
+            state: WidgetState::Normal,
+            metrics,
+        }
+    }
+}
+
+impl Vpn {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            icons: IconPack::default(),
+            format: Template::parse("{icon} {vpns}", PLACEHOLDERS).unwrap(),
+            required: vec![],
+        }
+    }
+
+    pub fn icons(mut self, icons: IconPack) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
+    }
+
+    /// Turn red while `interface` isn't up.
+    pub fn required(mut self, interface: &str) -> Self {
+        self.required.push(interface.to_string());
+        self
+    }
+
+    /// VPN interfaces that are up, sorted by name.
+    fn tunnels() -> Vec<Tunnel> {
+        let mut names: Vec<String> = fs::read_dir("/sys/class/net")
+            .map(|dir| {
+                dir.filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+
+        names
+            .into_iter()
+            .filter_map(|interface| {
+                let path = Path::new("/sys/class/net").join(&interface);
+                let read = |file: &str| fs::read_to_string(path.join(file)).unwrap_or_default();
+
+                let flags = u32::from_str_radix(read("flags").trim().trim_start_matches("0x"), 16);
+                if !matches!(flags, Ok(flags) if flags & IFF_UP != 0) {
+                    return None;
+                }
+
+                let kind = if read("uevent").lines().any(|l| l == "DEVTYPE=wireguard") {
+                    Kind::WireGuard
+                } else if path.join("tun_flags").exists() || read("type").trim() == TYPE_NONE {
+                    Kind::Tunnel
+                } else {
+                    return None;
+                };
+
+                let handshake = match kind {
+                    Kind::WireGuard => latest_handshake(&interface),
+                    Kind::Tunnel => None,
+                };
+                Some(Tunnel {
+                    interface,
+                    kind,
+                    handshake,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Age of the latest handshake from `wg show <interface> latest-handshakes`,
+/// `Some(None)` if no peer ever completed one. `None` if `wg` isn't
+/// installed or not allowed to read the interface.
+fn latest_handshake(interface: &str) -> Option<Option<Duration>> {
+    let output = Command::new("wg")
+        .args(["show", interface, "latest-handshakes"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let latest = match parse_handshakes(&String::from_utf8_lossy(&output.stdout)) {
+        Some(latest) => latest,
+        None => return Some(None),
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Some(Duration::from_secs(now.saturating_sub(latest))))
+}
+
+/// Unix time of the latest handshake in `latest-handshakes` output, a
+/// `<public key>\t<unix seconds>` line per peer with 0 for never. `None` if
+/// no peer ever completed one.
+fn parse_handshakes(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1)?.parse::<u64>().ok())
+        .filter(|time| *time != 0)
+        .max()
+}
+
+/// `45s`, `12m` or `3h`.
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 60 * 60 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}h", secs / 60 / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_of_every_peer() {
+        let output = "\
+xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\t1592913000
+HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=\t1592913240
+gN65BkIKy1eCE9pP1wdc8ROUtkHLF2PfAqYdyYBz6EA=\t0
+";
+        assert_eq!(parse_handshakes(output), Some(1_592_913_240));
+    }
+
+    #[test]
+    fn zero_is_never() {
+        let output = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\t0\n";
+        assert_eq!(parse_handshakes(output), None);
+        assert_eq!(parse_handshakes(""), None);
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let output = "\
+xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=\tyesterday
+gN65BkIKy1eCE9pP1wdc8ROUtkHLF2PfAqYdyYBz6EA=\t1592913000
+";
+        assert_eq!(parse_handshakes(output), Some(1_592_913_000));
+    }
+
+    #[test]
+    fn age_units() {
+        let age = |secs| format_age(Duration::from_secs(secs));
+        assert_eq!(age(0), "0s");
+        assert_eq!(age(59), "59s");
+        assert_eq!(age(60), "1m");
+        assert_eq!(age(60 * 60 - 1), "59m");
+        assert_eq!(age(60 * 60), "1h");
+        assert_eq!(age(60 * 60 * 30), "30h");
+    }
+}
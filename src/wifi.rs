@@ -1,15 +1,58 @@
 use async_trait::async_trait;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::process::Command;
+use smol::Task;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-use crate::{Widget, WidgetOutput};
+use crate::nl80211;
+use crate::{
+    Icon, IconPack, Metric, Template, TemplateError, Value, Widget, WidgetOutput, WidgetState,
+};
 use std::time::Duration;
 
+/// Current wireless connection, read from nl80211 and `/proc/net/wireless`.
+///
+/// Format placeholders: `{icon}` (signal ramp), `{ssid}`, `{quality}`
+/// (percent, from the signal level), `{bssid}`, `{signal}` (dBm), `{noise}`
+/// (dBm), `{link}` (link quality as reported by the driver), `{frequency}`
+/// (MHz), `{channel}`, `{tx_bitrate}`, `{rx_bitrate}` (Mb/s), `{interface}`.
+///
+/// Shows the first associated wireless interface unless set with
+/// `interface`.
 pub struct Wifi {
     interval: Duration,
+    interface: Option<String>,
+    icons: IconPack,
+    format: Template,
+
+    /// Opened on first use and again after an error
+    client: Arc<Mutex<Option<nl80211::Client>>>,
+}
+
+/// A row of `/proc/net/wireless`.
+#[derive(Debug, Copy, Clone)]
+struct Wireless {
+    link: f64,
+    /// dBm, `None` if the driver reports a relative level
+    level: Option<f64>,
+    /// dBm, `None` if the driver doesn't report it
+    noise: Option<f64>,
 }
 
+const PLACEHOLDERS: &[&str] = &[
+    "icon",
+    "ssid",
+    "quality",
+    "bssid",
+    "signal",
+    "frequency",
+    "channel",
+    "tx_bitrate",
+    "rx_bitrate",
+    "noise",
+    "link",
+    "interface",
+];
+
 #[async_trait]
 impl Widget for Wifi {
     fn interval(&self) -> Duration {
@@ -17,19 +60,68 @@ impl Widget for Wifi {
     }
 
     async fn get_output(&self) -> WidgetOutput {
-        let wifi = Self::get_wifi_ssid();
-        if wifi.is_none() {
+        let interfaces = match &self.interface {
+            Some(interface) => vec![interface.clone()],
+            None => wireless_interfaces(),
+        };
+        let client = self.client.clone();
+        // nl80211 calls block, keep them off the executor of the other widgets
+        let link = Task::blocking(async move {
+            let mut client = client.lock().unwrap();
+            interfaces.into_iter().find_map(|interface| {
+                Some((interface.clone(), Self::link(&mut client, &interface)?))
+            })
+        })
+        .await;
+
+        if let Some((interface, link)) = link {
+            let wireless = read_wireless().remove(&interface);
+            let signal = link.signal_dbm.map(f64::from).or_else(|| wireless?.level);
+            let quality = signal.map_or(0.0, quality_from_dbm);
+            let optional = |value: Option<f64>, precision| match value {
+                Some(value) => Value::Number(value, precision),
+                None => Value::Text(String::new()),
+            };
+
+            let mut metrics = vec![];
+            if let Some(signal) = signal {
+                metrics.push(
+                    Metric::gauge(
+                        "wifi_signal_dbm",
+                        "Signal level of the wireless connection in dBm",
+                        signal,
+                    )
+                    .label("interface", interface.as_str()),
+                );
+            }
+
             WidgetOutput {
-                text: "<span foreground='grey'>Not Connected</span>".to_string(),
-                use_default_foreground: false,
+                text: self.format.render(|name| match name {
+                    "icon" => self.icons.icon(Icon::Wifi, quality).into(),
+                    "ssid" => link.ssid.as_str().into(),
+                    "bssid" => link.bssid().into(),
+                    "signal" => optional(signal, 0),
+                    "noise" => optional(wireless.and_then(|w| w.noise), 0),
+                    "link" => optional(wireless.map(|w| w.link), 0),
+                    "interface" => interface.as_str().into(),
+                    "frequency" => optional(link.frequency.map(f64::from), 0),
+                    "channel" => optional(link.channel().map(f64::from), 0),
+                    "tx_bitrate" => optional(link.tx_bitrate, 1),
+                    "rx_bitrate" => optional(link.rx_bitrate, 1),
+                    _ => quality.into(),
+                }),
+                use_default_foreground: true,
                 use_default_background: true,
+                state: WidgetState::Normal,
+                metrics,
             }
         } else {
-            let quality = Self::get_current_wifi_quality().unwrap().abs();
             WidgetOutput {
-                text: format!("{} - {:.0}", wifi.unwrap(), quality),
-                use_default_foreground: true,
+                text: "<span foreground='grey'>Not Connected</span>".to_string(),
+                use_default_foreground: false,
                 use_default_background: true,
+                state: WidgetState::State("disconnected"),
+                metrics: vec![],
             }
         }
     }
@@ -37,38 +129,175 @@ impl Widget for Wifi {
 
 impl Wifi {
     pub fn new(interval: Duration) -> Self {
-        Self { interval }
+        Self {
+            interval,
+            interface: None,
+            icons: IconPack::default(),
+            format: Template::parse("{ssid} - {quality}", PLACEHOLDERS).unwrap(),
+            client: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Show `interface` instead of the first associated one.
+    pub fn interface(mut self, interface: &str) -> Self {
+        self.interface = Some(interface.to_string());
+        self
+    }
+
+    pub fn icons(mut self, icons: IconPack) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Result<Self, TemplateError> {
+        self.format = Template::parse(format, PLACEHOLDERS)?;
+        Ok(self)
     }
 
     pub async fn stream_output(&self) {}
 
-    fn get_current_wifi_quality() -> Option<f64> {
-        let file = File::open("/proc/net/wireless").ok()?;
-        let reader = BufReader::new(file);
+    /// Link of `interface` over the shared client, `None` if it isn't
+    /// associated or nl80211 failed.
+    fn link(client: &mut Option<nl80211::Client>, interface: &str) -> Option<nl80211::Link> {
+        if client.is_none() {
+            *client = nl80211::Client::open().ok();
+        }
+        match client.as_mut()?.link(interface) {
+            Ok(link) => link,
+            Err(_) => {
+                *client = None;
+                None
+            }
+        }
+    }
+}
+
+/// Percentage of a signal level between -100 dBm (0%) and -50 dBm (100%).
+fn quality_from_dbm(dbm: f64) -> f64 {
+    (2.0 * (dbm + 100.0)).clamp(0.0, 100.0)
+}
+
+fn read_wireless() -> HashMap<String, Wireless> {
+    let table = std::fs::read_to_string("/proc/net/wireless").unwrap_or_default();
+    parse_wireless(&table)
+}
+
+/// Parse `/proc/net/wireless` by interface, each row being `iface: status
+/// link level noise ...` after two header lines. Values carry a trailing `.`
+/// when they were updated since the last read.
+///
+/// The kernel already converts levels in dBm to negative values, anything
+/// else is a relative level of the driver.
+fn parse_wireless(table: &str) -> HashMap<String, Wireless> {
+    table
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let mut split = line.splitn(2, ':');
+            let interface = split.next()?.trim().to_string();
+            // The status is hex, and only the quality columns are of interest
+            let mut fields = split
+                .next()?
+                .split_whitespace()
+                .skip(1)
+                .map(|field| field.trim_end_matches('.').parse::<f64>().ok());
+            let (link, level, noise) = (fields.next()??, fields.next()??, fields.next()??);
+
+            let dbm = |value: f64| if value < 0.0 { Some(value) } else { None };
+            Some((
+                interface,
+                Wireless {
+                    link,
+                    level: dbm(level),
+                    // -256 is a noise of 0 in dBm, which means unknown
+                    noise: dbm(noise).filter(|noise| *noise > -256.0),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Interfaces with a `wireless` directory in sysfs, sorted by name.
+fn wireless_interfaces() -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir("/sys/class/net")
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter(|name| is_wireless(name))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// Whether `interface` is a wireless one, without asking nl80211.
+pub(crate) fn is_wireless(interface: &str) -> bool {
+    std::path::Path::new("/sys/class/net")
+        .join(interface)
+        .join("wireless")
+        .exists()
+}
+
+/// SSID `interface` is connected to, `None` for wired interfaces.
+pub(crate) fn ssid(interface: &str) -> Option<String> {
+    Some(nl80211::link(interface).ok()??.ssid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str =
+        "Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE
+ face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22
+";
+
+    #[test]
+    fn parses_every_adapter() {
+        let table = format!(
+            "{}{}{}",
+            HEADER,
+            " wlan0: 0000   54.  -56.  -256        0      0      0      0      0        0\n",
+            "wlp3s0: 00af   70   -40   -95.       0      0      0     12      0        0\n",
+        );
+        let table = parse_wireless(&table);
+        assert_eq!(table.len(), 2);
 
-        let quality: String = reader
-            .lines()
-            .nth(2)?
-            .unwrap()
-            .split_whitespace()
-            .map(Into::into)
-            .nth(3)?;
+        let wlan0 = table["wlan0"];
+        assert_eq!(wlan0.link, 54.0);
+        assert_eq!(wlan0.level, Some(-56.0));
+        assert_eq!(wlan0.noise, None);
 
-        Some(quality.parse::<f64>().unwrap() * (10.0 / 7.0))
+        // A status with hex digits doesn't drop the row
+        let wlp3s0 = table["wlp3s0"];
+        assert_eq!(wlp3s0.link, 70.0);
+        assert_eq!(wlp3s0.level, Some(-40.0));
+        assert_eq!(wlp3s0.noise, Some(-95.0));
     }
 
-    fn get_wifi_ssid() -> Option<String> {
-        let output = Command::new("sh")
-            .arg("-c")
-            .arg("iwgetid -r")
-            .output()
-            .expect("Failed to execute `iwgetid`")
-            .stdout;
-        let output = String::from_utf8(output).unwrap().trim().to_owned();
-        if output.is_empty() {
-            None
-        } else {
-            Some(output)
-        }
+    #[test]
+    fn relative_levels_are_not_dbm() {
+        let table = format!(
+            "{}{}",
+            HEADER, "  eth1: 0000   40.  200.   10.       0      0      0      0      0        0\n"
+        );
+        let eth1 = parse_wireless(&table)["eth1"];
+        assert_eq!(eth1.link, 40.0);
+        assert_eq!(eth1.level, None);
+        assert_eq!(eth1.noise, None);
+    }
+
+    #[test]
+    fn skips_malformed_rows() {
+        let table = format!("{}{}", HEADER, " wlan0: 0000   54.\n");
+        assert!(parse_wireless(&table).is_empty());
+        assert!(parse_wireless("").is_empty());
+    }
+
+    #[test]
+    fn quality_from_signal() {
+        assert_eq!(quality_from_dbm(-56.0), 88.0);
+        assert_eq!(quality_from_dbm(-30.0), 100.0);
+        assert_eq!(quality_from_dbm(-110.0), 0.0);
     }
 }